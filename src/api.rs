@@ -1,24 +1,151 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
-    routing::{delete, get, post},
+    extract::{DefaultBodyLimit, FromRef, Path, Query, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use futures_util::stream::{self, Stream};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::str::FromStr;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 
-use crate::map::{Map, Point};
+use crate::bulk::{self, BulkBudget, BulkInput, BulkItem, BulkOperation, BulkOutcome};
+use crate::graph::{
+    BudgetExceeded, Budgeted, CancelToken, NoObserver, SearchBudget, SearchObserver, TraceEvent,
+    TraceRecorder,
+};
+use crate::hpa::Hierarchy;
+use crate::jobs::{JobConfig, JobQueue, JobView, SubmitError};
+use crate::layered::{LayeredMap, Point3};
+use crate::map::{
+    Algorithm, Cell, Connectivity, DistanceGrid, GridKind, Map, Point, SearchOptions,
+};
+use crate::map_components::Component;
+use crate::map_constraints::{Constraint, PathConstraints, Region};
+use crate::map_diameter::Diameter;
+use crate::map_formatter::distance_matrix_csv;
+use crate::map_parser::{MapLimits, ParseMapError};
+use crate::map_pursuit::TargetPolicy;
+use crate::metrics::{self, MazeMetrics};
+use crate::multi_agent::{self, Agent, PlanError};
+use crate::planner::Planner;
+use crate::sessions::{CreateError, SessionConfig, SessionStore};
+use crate::solve_cache::{CacheStats, SolveCache, SolveKey};
 
 // API Version
 const API_VERSION: &str = "v1";
 
 // In-memory storage for maps
-pub type MapStore = Arc<RwLock<HashMap<Uuid, Map>>>;
+pub type MapStore = Arc<RwLock<HashMap<Uuid, StoredMap>>>;
+
+// Stored map together with the data derived from it
+pub struct StoredMap {
+    // Shared with the work running on it, so reading never copies the map;
+    // an edit replaces it
+    pub map: Arc<Map>,
+    // Incremented on every edit of the map
+    pub version: u64,
+    // HPA* abstraction, built by the first HPA* query on a blocking thread
+    // and dropped whenever the walls change
+    pub hierarchy: Arc<OnceLock<Hierarchy>>,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub maps: MapStore,
+    pub sessions: SessionStore,
+    pub jobs: JobQueue,
+    pub map_limits: MapLimits,
+    pub solve_cache: SolveCache,
+}
+
+// Server settings that deployments may want to tune
+#[derive(Debug, Clone, Default)]
+pub struct ApiConfig {
+    pub jobs: JobConfig,
+    pub sessions: SessionConfig,
+    // Largest map accepted from clients, checked while parsing
+    pub map_limits: MapLimits,
+    pub body_limit: BodyLimit,
+    pub solve_cache_capacity: CacheCapacity,
+}
+
+// Largest request body in bytes
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit(pub usize);
+
+impl Default for BodyLimit {
+    fn default() -> Self {
+        BodyLimit(8 * 1024 * 1024)
+    }
+}
+
+// Bytes of solved paths kept across requests; 0 turns the cache off
+#[derive(Debug, Clone, Copy)]
+pub struct CacheCapacity(pub usize);
+
+impl Default for CacheCapacity {
+    fn default() -> Self {
+        CacheCapacity(64 * 1024 * 1024)
+    }
+}
+
+impl FromRef<AppState> for MapStore {
+    fn from_ref(state: &AppState) -> Self {
+        state.maps.clone()
+    }
+}
+
+impl FromRef<AppState> for SessionStore {
+    fn from_ref(state: &AppState) -> Self {
+        state.sessions.clone()
+    }
+}
+
+impl FromRef<AppState> for JobQueue {
+    fn from_ref(state: &AppState) -> Self {
+        state.jobs.clone()
+    }
+}
+
+impl FromRef<AppState> for MapLimits {
+    fn from_ref(state: &AppState) -> Self {
+        state.map_limits
+    }
+}
+
+impl FromRef<AppState> for SolveCache {
+    fn from_ref(state: &AppState) -> Self {
+        state.solve_cache.clone()
+    }
+}
+
+impl StoredMap {
+    pub fn new(map: Map) -> Self {
+        StoredMap {
+            map: Arc::new(map),
+            version: 0,
+            hierarchy: Arc::default(),
+        }
+    }
+
+    pub fn update(&mut self, map: Map) {
+        // Searches still running keep the abstraction of the old map
+        self.hierarchy = Arc::default();
+        self.map = Arc::new(map);
+        self.version += 1;
+    }
+}
 
 // API Response types
 #[derive(Serialize)]
@@ -29,12 +156,18 @@ pub struct ApiResponse<T> {
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub error: String,
+    // Stable machine-readable reason for errors that clients are expected
+    // to handle, e.g. "budget_exceeded"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
 }
 
 // Request/Response DTOs
 #[derive(Deserialize)]
 pub struct CreateMapRequest {
     pub map_string: String,
+    #[serde(default)]
+    pub require_connected: bool,
 }
 
 #[derive(Serialize)]
@@ -49,18 +182,418 @@ pub struct SolveQuery {
     pub start_y: usize,
     pub finish_x: usize,
     pub finish_y: usize,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    #[serde(default)]
+    pub any_angle: bool,
+    #[serde(default)]
+    pub trace: bool,
+    // Search budget: the most cells to expand and a time limit, which is
+    // capped by the server
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+    // Path constraints for this request only: cells treated as walls as
+    // "x,y;x,y", a region the path must cross as "x,y,rows,cols" and the
+    // most moves allowed
+    pub avoid: Option<String>,
+    pub region: Option<String>,
+    pub max_length: Option<usize>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct SolveResponse {
     pub solution_map: String,
     pub path_found: bool,
+    pub path: Vec<PointDto>,
+    // Any-angle polyline through cell centres, see `Map::smooth_path`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub waypoints: Option<Vec<[f64; 2]>>,
+    // Search events in order as [kind, x, y]: kind 0 - pushed to the
+    // frontier, 1 - popped and expanded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Vec<[usize; 3]>>,
+    // Set when a path exists on the map but the constraints rule it out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violated_constraint: Option<Constraint>,
+}
+
+// Periodic snapshot of a streamed search
+#[derive(Serialize)]
+pub struct SolveProgress {
+    // Cells expanded so far
+    pub visited: usize,
+    // Cells waiting in the queue or heap
+    pub frontier: usize,
+    // Smallest wall-ignoring estimate from a visited cell to the finish,
+    // in path cost units (10 per straight move, 14 per diagonal)
+    pub best_distance: Option<u32>,
+}
+
+// Work accepted by the job queue, tagged by `kind`
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobRequest {
+    Solve {
+        map_id: Uuid,
+        start: PointDto,
+        finish: PointDto,
+        #[serde(default)]
+        algorithm: Algorithm,
+        #[serde(default)]
+        connectivity: Connectivity,
+    },
+    // Generates a maze and stores it as a new map
+    Generate {
+        rows: usize,
+        cols: usize,
+        seed: Option<u64>,
+    },
+    Analysis {
+        map_id: Uuid,
+        analysis: AnalysisKind,
+    },
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisKind {
+    Metrics,
+    Diameter,
+}
+
+#[derive(Deserialize)]
+pub struct CreateJobRequest {
+    #[serde(flatten)]
+    pub job: JobRequest,
+    // Budget of the job, capped by the server limits
+    pub max_steps: Option<usize>,
+    pub time_limit_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct LayeredSolveRequest {
+    pub map_string: String,
+    pub start: PointDto,
+    pub finish: PointDto,
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct BatchPairDto {
+    pub start: PointDto,
+    pub finish: PointDto,
+}
+
+#[derive(Deserialize)]
+pub struct BatchSolveRequest {
+    pub pairs: Vec<BatchPairDto>,
+    // Only bfs and dijkstra, which build a full search tree per start
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    // Spreads the distinct starts over all cores
+    #[serde(default)]
+    pub parallel: bool,
+    // Budget of the search from each distinct start
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+// Result for one pair, in the order of the request
+#[derive(Serialize)]
+pub struct BatchRouteDto {
+    pub path_found: bool,
+    pub path: Vec<PointDto>,
+    // Moves for bfs, path cost for dijkstra
+    pub cost: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct NamedPointDto {
+    pub name: String,
+    pub x: usize,
+    pub y: usize,
+}
+
+#[derive(Deserialize)]
+pub struct DistanceMatrixRequest {
+    pub points: Vec<NamedPointDto>,
+    // bfs counts moves, dijkstra sums move costs
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    // Budget of the search from each point
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatrixFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Deserialize)]
+pub struct DistanceMatrixQuery {
+    #[serde(default)]
+    pub format: MatrixFormat,
+}
+
+// Row i, column j is the distance from point i to point j; null if the
+// point cannot be reached
+#[derive(Serialize)]
+pub struct DistanceMatrixResponse {
+    pub names: Vec<String>,
+    pub distances: Vec<Vec<Option<u32>>>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkOperationKind {
+    Validate,
+    Metrics,
+    Solve,
+}
+
+// Runs one operation over every stored map; solve uses the start and end
+// declared in each map
+#[derive(Deserialize)]
+pub struct BulkRequest {
+    pub operation: BulkOperationKind,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    // Budget of each map's metrics or search, the time limit is capped by
+    // the server
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct DistancesQuery {
+    pub from: String,
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct DistancesResponse {
+    pub distances: DistanceGrid,
+    pub heatmap: String,
+}
+
+#[derive(Deserialize)]
+pub struct ComponentsQuery {
+    pub start: Option<String>,
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ComponentsResponse {
+    pub components: Vec<Component>,
+    pub labels: Vec<Vec<Option<usize>>>,
+    pub connected: bool,
+    pub start_component: Option<usize>,
+    pub unreachable_components: Vec<usize>,
+}
+
+// Optional limits of an analysis request, as for a solve
+#[derive(Deserialize)]
+pub struct BudgetQuery {
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct MetricsQuery {
+    pub start: Option<String>,
+    pub finish: Option<String>,
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct PointDto {
     pub x: usize,
     pub y: usize,
+    // Layer of a layered map; absent for flat maps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub z: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateSessionRequest {
+    pub start: PointDto,
+    pub finish: PointDto,
+    // Budget of the initial planning
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct CellChangeDto {
+    pub x: usize,
+    pub y: usize,
+    pub wall: bool,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateCellsRequest {
+    pub cells: Vec<CellChangeDto>,
+    // Budget of the repair; when it runs out the session is left unchanged
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub path_found: bool,
+    pub path: Vec<PointDto>,
+    pub solution_map: String,
+    // Nodes expanded by the last (re)planning
+    pub expanded: usize,
+}
+
+#[derive(Deserialize)]
+pub struct AgentDto {
+    pub start: PointDto,
+    pub finish: PointDto,
+}
+
+#[derive(Deserialize)]
+pub struct PlanAgentsRequest {
+    pub agents: Vec<AgentDto>,
+    // Budget shared by all agents, counted in expanded (cell, time) states
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct TimedPointDto {
+    pub x: usize,
+    pub y: usize,
+    pub t: usize,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetPolicyDto {
+    #[default]
+    Route,
+    Flee,
+}
+
+#[derive(Deserialize)]
+pub struct PursuitRequest {
+    pub pursuer: PointDto,
+    pub target: PointDto,
+    #[serde(default)]
+    pub policy: TargetPolicyDto,
+    // Target positions at steps 1, 2, ... (route policy only)
+    #[serde(default)]
+    pub route: Vec<PointDto>,
+    // Budget shared by the searches of the pursuer and of a fleeing target
+    pub max_expanded: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct PursuitResponse {
+    pub caught: bool,
+    pub time: Option<usize>,
+    pub path: Vec<TimedPointDto>,
+    pub target_path: Vec<TimedPointDto>,
+    pub solution_map: String,
+}
+
+#[derive(Serialize)]
+pub struct PlanAgentsResponse {
+    // Positions of every agent at every time step, padded to the makespan
+    pub paths: Vec<Vec<TimedPointDto>>,
+    pub makespan: usize,
+    pub frames: Vec<String>,
+}
+
+impl SessionResponse {
+    fn new(id: Uuid, planner: &Planner) -> Self {
+        let path = planner.path();
+        let mut solution = planner.map().clone();
+        if let Some(path) = &path {
+            solution.mark_path(path);
+        }
+
+        SessionResponse {
+            id,
+            path_found: path.is_some(),
+            path: path
+                .unwrap_or_default()
+                .into_iter()
+                .map(PointDto::from)
+                .collect(),
+            solution_map: solution.to_string(),
+            expanded: planner.expanded,
+        }
+    }
+}
+
+impl From<Point> for PointDto {
+    fn from(point: Point) -> Self {
+        PointDto {
+            x: point.x,
+            y: point.y,
+            z: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct DiameterResponse {
+    pub start: PointDto,
+    pub finish: PointDto,
+    pub length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_string: Option<String>,
+}
+
+impl From<Diameter> for DiameterResponse {
+    fn from(diameter: Diameter) -> Self {
+        DiameterResponse {
+            start: diameter.start.into(),
+            finish: diameter.finish.into(),
+            length: diameter.length,
+            map_string: None,
+        }
+    }
+}
+
+impl From<Point3> for PointDto {
+    fn from(point: Point3) -> Self {
+        PointDto {
+            x: point.x,
+            y: point.y,
+            z: Some(point.z),
+        }
+    }
+}
+
+impl From<PointDto> for Point3 {
+    fn from(dto: PointDto) -> Self {
+        Point3 {
+            x: dto.x,
+            y: dto.y,
+            z: dto.z.unwrap_or(0),
+        }
+    }
 }
 
 impl From<PointDto> for Point {
@@ -69,6 +602,12 @@ impl From<PointDto> for Point {
     }
 }
 
+impl SolveQuery {
+    fn has_constraints(&self) -> bool {
+        self.avoid.is_some() || self.region.is_some() || self.max_length.is_some()
+    }
+}
+
 impl From<SolveQuery> for (Point, Point) {
     fn from(query: SolveQuery) -> Self {
         (
@@ -85,35 +624,138 @@ impl From<SolveQuery> for (Point, Point) {
 }
 
 // API Routes
+#[allow(dead_code)] // used in tests
 pub fn create_api_router(map_store: MapStore) -> Router {
+    create_api_router_with_config(map_store, ApiConfig::default())
+}
+
+// Must be called inside a tokio runtime: the job workers start right away
+pub fn create_api_router_with_config(map_store: MapStore, config: ApiConfig) -> Router {
+    let BodyLimit(body_limit) = config.body_limit;
     Router::new()
         .route(&format!("/{}/maps", API_VERSION), post(create_map))
         .route(&format!("/{}/maps", API_VERSION), get(list_maps))
         .route(&format!("/{}/maps/:id", API_VERSION), get(get_map))
         .route(&format!("/{}/maps/:id", API_VERSION), delete(delete_map))
+        .route(&format!("/{}/maps/:id", API_VERSION), put(update_map))
         .route(&format!("/{}/maps/:id/solve", API_VERSION), get(solve_map)) //TODO: переписать на GET
-        .with_state(map_store)
+        .route(
+            &format!("/{}/maps/:id/solve/stream", API_VERSION),
+            get(stream_solve),
+        )
+        .route(
+            &format!("/{}/maps/:id/solve/batch", API_VERSION),
+            post(solve_batch),
+        )
+        .route(
+            &format!("/{}/maps/:id/distances", API_VERSION),
+            get(map_distances),
+        )
+        .route(
+            &format!("/{}/maps/:id/distances/matrix", API_VERSION),
+            post(distance_matrix),
+        )
+        .route(
+            &format!("/{}/maps/:id/components", API_VERSION),
+            get(map_components),
+        )
+        .route(
+            &format!("/{}/maps/:id/metrics", API_VERSION),
+            get(map_metrics),
+        )
+        .route(
+            &format!("/{}/maps/:id/diameter", API_VERSION),
+            get(map_diameter).post(place_endpoints),
+        )
+        .route(
+            &format!("/{}/maps/:id/sessions", API_VERSION),
+            post(create_session),
+        )
+        .route(
+            &format!("/{}/maps/:id/agents", API_VERSION),
+            post(plan_agents),
+        )
+        .route(
+            &format!("/{}/maps/:id/pursuit", API_VERSION),
+            post(pursue_target),
+        )
+        .route(
+            &format!("/{}/layered/solve", API_VERSION),
+            post(solve_layered),
+        )
+        .route(
+            &format!("/{}/cache/stats", API_VERSION),
+            get(solve_cache_stats),
+        )
+        .route(&format!("/{}/admin/bulk", API_VERSION), post(run_bulk))
+        .route(&format!("/{}/jobs", API_VERSION), post(create_job))
+        .route(
+            &format!("/{}/jobs/:id", API_VERSION),
+            get(get_job).delete(cancel_job),
+        )
+        .route(
+            &format!("/{}/sessions/:id", API_VERSION),
+            get(get_session).delete(delete_session),
+        )
+        .route(
+            &format!("/{}/sessions/:id/cells", API_VERSION),
+            patch(update_session_cells),
+        )
+        .with_state(AppState {
+            maps: map_store,
+            sessions: SessionStore::new(config.sessions),
+            jobs: JobQueue::start(config.jobs),
+            map_limits: config.map_limits,
+            solve_cache: SolveCache::new(config.solve_cache_capacity.0),
+        })
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(middleware::map_response(
+            move |response: Response| async move { body_limit_error(response, body_limit) },
+        ))
 }
 
 // Handler functions
 async fn create_map(
     State(map_store): State<MapStore>,
+    State(limits): State<MapLimits>,
+    Json(payload): Json<CreateMapRequest>,
+) -> Result<Json<ApiResponse<MapResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = parse_map(&payload, &limits).await?;
+    let id = Uuid::new_v4();
+
+    // Store the map
+    map_store.write().await.insert(id, StoredMap::new(map)); //TODO: переделть
+
+    Ok(Json(ApiResponse {
+        data: MapResponse {
+            id,
+            map_string: payload.map_string,
+        },
+    }))
+}
+
+// Replaces the stored map; derived data is rebuilt
+async fn update_map(
+    Path(id): Path<Uuid>,
+    State(map_store): State<MapStore>,
+    State(limits): State<MapLimits>,
+    State(cache): State<SolveCache>,
     Json(payload): Json<CreateMapRequest>,
 ) -> Result<Json<ApiResponse<MapResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    // Parse map from string
-    let map = Map::from_str(&payload.map_string).map_err(|_| {
+    let map = parse_map(&payload, &limits).await?;
+
+    let mut maps = map_store.write().await;
+    let stored = maps.get_mut(&id).ok_or_else(|| {
         (
-            StatusCode::BAD_REQUEST,
+            StatusCode::NOT_FOUND,
             Json(ErrorResponse {
-                error: "Invalid map format".to_string(),
+                error: "Map not found".to_string(),
+                code: None,
             }),
         )
     })?;
-
-    let id = Uuid::new_v4();
-
-    // Store the map
-    map_store.write().await.insert(id, map); //TODO: переделть
+    stored.update(map);
+    cache.invalidate_map(id);
 
     Ok(Json(ApiResponse {
         data: MapResponse {
@@ -129,9 +771,9 @@ async fn list_maps(
     let maps = map_store.read().await;
     let response: Vec<MapResponse> = maps
         .iter()
-        .map(|(id, map)| MapResponse {
+        .map(|(id, stored)| MapResponse {
             id: *id,
-            map_string: map.to_string(),
+            map_string: stored.map.to_string(),
         })
         .collect();
 
@@ -143,11 +785,12 @@ async fn get_map(
     State(map_store): State<MapStore>,
 ) -> Result<Json<ApiResponse<MapResponse>>, (StatusCode, Json<ErrorResponse>)> {
     let maps = map_store.read().await;
-    let map = maps.get(&id).ok_or_else(|| {
+    let map = maps.get(&id).map(|stored| &stored.map).ok_or_else(|| {
         (
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
                 error: "Map not found".to_string(),
+                code: None,
             }),
         )
     })?;
@@ -163,16 +806,19 @@ async fn get_map(
 async fn delete_map(
     Path(id): Path<Uuid>,
     State(map_store): State<MapStore>,
+    State(cache): State<SolveCache>,
 ) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
     let mut maps = map_store.write().await;
 
     if maps.remove(&id).is_some() {
+        cache.invalidate_map(id);
         Ok(StatusCode::NO_CONTENT)
     } else {
         Err((
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
                 error: "Map not found".to_string(),
+                code: None,
             }),
         ))
     }
@@ -182,49 +828,1539 @@ async fn solve_map(
     Path(id): Path<Uuid>,
     Query(params): Query<SolveQuery>,
     State(map_store): State<MapStore>,
+    State(cache): State<SolveCache>,
 ) -> Result<Json<ApiResponse<SolveResponse>>, (StatusCode, Json<ErrorResponse>)> {
-    // Create a clone to work with
-    let mut map_clone = {
-        let mut maps = map_store.write().await;
-        let map = maps.get_mut(&id).ok_or_else(|| {
+    let options = SearchOptions {
+        algorithm: params.algorithm,
+        connectivity: params.connectivity,
+    };
+    let any_angle = params.any_angle;
+    let trace = params.trace;
+    let constrained = params.has_constraints();
+
+    // Traces are long and asked for rarely, and constraints make almost
+    // every request unique, so both bypass the cache
+    let (map_clone, hierarchy, key) = {
+        let maps = map_store.read().await;
+        let stored = maps.get(&id).ok_or_else(|| {
             (
                 StatusCode::NOT_FOUND,
                 Json(ErrorResponse {
                     error: "Map not found".to_string(),
+                    code: None,
                 }),
             )
         })?;
-        map.clone()
-    };
 
+        let key = (!trace && !constrained).then_some(SolveKey {
+            map_id: id,
+            version: stored.version,
+            start: Point {
+                x: params.start_x,
+                y: params.start_y,
+            },
+            finish: Point {
+                x: params.finish_x,
+                y: params.finish_y,
+            },
+            algorithm: options.algorithm,
+            connectivity: options.connectivity,
+            any_angle,
+        });
+        (stored.map.clone(), stored.hierarchy.clone(), key)
+    };
+    // A hit is rendered outside the lock, like a fresh result
+    if let Some(path) = key.as_ref().and_then(|key| cache.get(key)) {
+        let data = solve_response(&map_clone, path, any_angle, None);
+        return Ok(Json(ApiResponse { data }));
+    }
+    let budget = SearchBudget {
+        cancel: Some(CancelToken::default()),
+        ..solve_budget(params.max_expanded, params.timeout_ms)
+    };
+    let constraints = path_constraints(&map_clone, &params)?;
     let (start, end): (Point, Point) = params.into();
 
+    validate_solve(&map_clone, start, end, options, any_angle)?;
+
+    if trace && !searches_cells(&map_clone, options.algorithm) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Search trace is not supported for jps and hpa".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    // The search runs off the async workers and stops if the client goes
+    // away before it finishes
+    let _cancel_on_drop = budget.cancel.clone().map(CancelOnDrop);
+    let (map_clone, result, recorder) = tokio::task::spawn_blocking(move || {
+        let mut recorder = TraceRecorder::with_limit(TRACE_EVENT_LIMIT);
+        let result = match &constraints {
+            Some(constraints) => map_clone
+                .search_constrained(start, end, options, constraints, &hierarchy, &budget)
+                .map(|constrained| (constrained.result, constrained.violated)),
+            None if trace => map_clone
+                .search_cached(start, end, options, &hierarchy, &budget, &mut recorder)
+                .map(|result| (result, None)),
+            None => map_clone
+                .search_cached(start, end, options, &hierarchy, &budget, &mut NoObserver)
+                .map(|result| (result, None)),
+        };
+        (map_clone, result, recorder)
+    })
+    .await
+    .expect("search task panicked");
+    // A cut-off trace stops the search too, so its result is incomplete
+    if recorder.truncated {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                error: format!("Search trace exceeds {} events", TRACE_EVENT_LIMIT),
+                code: Some("trace_too_long"),
+            }),
+        ));
+    }
+    let (result, violated_constraint) = result.map_err(budget_exceeded)?;
+
+    let trace = trace.then(|| {
+        recorder
+            .events
+            .into_iter()
+            .map(|event| match event {
+                TraceEvent::Push(point) => [0, point.x, point.y],
+                TraceEvent::Pop(point) => [1, point.x, point.y],
+            })
+            .collect()
+    });
+
+    if let Some(key) = key {
+        cache.insert(key, result.path.clone());
+    }
+    let data = SolveResponse {
+        violated_constraint,
+        ..solve_response(&map_clone, result.path, any_angle, trace)
+    };
+
+    Ok(Json(ApiResponse { data }))
+}
+
+// Most events a solve with `trace=true` records; each one is about 14 bytes
+// of JSON, so a trace stays within a couple of megabytes
+const TRACE_EVENT_LIMIT: usize = 100_000;
+
+// Upper bound on the wall-clock time of a single solve request
+const SOLVE_TIME_LIMIT: Duration = Duration::from_secs(10);
+
+// Limits of a solve request; the time limit can only be lowered
+fn solve_budget(max_expanded: Option<usize>, timeout_ms: Option<u64>) -> SearchBudget {
+    let time_limit = timeout_ms.map_or(SOLVE_TIME_LIMIT, |timeout| {
+        Duration::from_millis(timeout).min(SOLVE_TIME_LIMIT)
+    });
+    SearchBudget {
+        max_expanded,
+        deadline: Some(Instant::now() + time_limit),
+        cancel: None,
+    }
+}
+
+// Cancels the search when the request future is dropped, e.g. because the
+// client has disconnected
+struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+// Runs budgeted work off the async workers; it stops when the request is
+// dropped, and a budget that runs out becomes a 422
+async fn run_budgeted<T: Send + 'static>(
+    max_expanded: Option<usize>,
+    timeout_ms: Option<u64>,
+    work: impl FnOnce(&SearchBudget) -> Result<T, BudgetExceeded> + Send + 'static,
+) -> Result<T, (StatusCode, Json<ErrorResponse>)> {
+    let budget = SearchBudget {
+        cancel: Some(CancelToken::default()),
+        ..solve_budget(max_expanded, timeout_ms)
+    };
+    let _cancel_on_drop = budget.cancel.clone().map(CancelOnDrop);
+    tokio::task::spawn_blocking(move || work(&budget))
+        .await
+        .expect("search task panicked")
+        .map_err(budget_exceeded)
+}
+
+fn budget_exceeded(exceeded: BudgetExceeded) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(ErrorResponse {
+            error: exceeded.to_string(),
+            code: Some("budget_exceeded"),
+        }),
+    )
+}
+
+// Checks shared by the plain and the streaming solve
+fn validate_solve(
+    map: &Map,
+    start: Point,
+    end: Point,
+    options: SearchOptions,
+    any_angle: bool,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
     // Check if start and end are not walls
-    if !map_clone.validate_coordinates(start) {
+    if !map.validate_coordinates(start) {
         return Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
                 error: "Start position  is invalid".to_string(),
+                code: None,
             }),
         ));
     }
 
-    if !map_clone.validate_coordinates(end) {
+    if !map.validate_coordinates(end) {
         return Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
                 error: "End position is invalid".to_string(),
+                code: None,
             }),
         ));
     }
 
-    // Find and mark the path
-    let path_found = map_clone.find_and_mark_path(start, end);
-
-    Ok(Json(ApiResponse {
-        data: SolveResponse {
-            solution_map: map_clone.to_string(),
+    // The cached abstraction only covers 4-connected moves
+    if options.algorithm == Algorithm::Hpa && options.connectivity != Connectivity::Four {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "HPA* supports only 4-connected maps".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    // Diagonal moves and straight lines of sight are defined for square cells
+    if map.kind == GridKind::Hex && options.connectivity != Connectivity::Four {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Connectivity 8 is not supported on hex maps".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    if map.kind == GridKind::Hex && any_angle {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Any-angle paths are not supported on hex maps".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    Ok(())
+}
+
+// JPS and HPA* search over jump points and cluster entrances, not cells
+fn searches_cells(map: &Map, algorithm: Algorithm) -> bool {
+    map.kind == GridKind::Hex || !matches!(algorithm, Algorithm::Jps | Algorithm::Hpa)
+}
+
+// Reads the path constraints of a solve request, if it has any
+fn path_constraints(
+    map: &Map,
+    params: &SolveQuery,
+) -> Result<Option<PathConstraints>, (StatusCode, Json<ErrorResponse>)> {
+    if !params.has_constraints() {
+        return Ok(None);
+    }
+
+    let bad_request = |error: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse { error, code: None }),
+        )
+    };
+
+    // The trace would show the search on the blocked copy, and a smoothed
+    // line may cut through an avoided cell
+    if params.any_angle || params.trace {
+        return Err(bad_request(
+            "Path constraints cannot be combined with trace or any_angle".to_string(),
+        ));
+    }
+
+    let in_bounds = |point: &Point| point.x < map.rows && point.y < map.cols;
+    let forbidden = params
+        .avoid
+        .iter()
+        .flat_map(|avoid| avoid.split(';'))
+        .filter(|cell| !cell.trim().is_empty())
+        .map(|cell| {
+            Point::from_str(cell)
+                .ok()
+                .filter(in_bounds)
+                .ok_or_else(|| bad_request(format!("Avoided cell '{}' is invalid", cell)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let region = params
+        .region
+        .as_deref()
+        .map(|region| {
+            let numbers: Vec<usize> = region
+                .split(',')
+                .map(|number| number.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| bad_request("Region is invalid".to_string()))?;
+            match numbers[..] {
+                [x, y, rows, cols]
+                    if in_bounds(&Point { x, y })
+                        && (1..=map.rows).contains(&rows)
+                        && (1..=map.cols).contains(&cols) =>
+                {
+                    Ok(Region { x, y, rows, cols })
+                }
+                _ => Err(bad_request("Region is invalid".to_string())),
+            }
+        })
+        .transpose()?;
+
+    Ok(Some(PathConstraints {
+        forbidden,
+        region,
+        max_length: params.max_length,
+    }))
+}
+
+// Marks the path on the map and packs the result
+fn solve_response(
+    map: &Map,
+    path: Option<Vec<Point>>,
+    any_angle: bool,
+    trace: Option<Vec<[usize; 3]>>,
+) -> SolveResponse {
+    let path_found = path.is_some();
+    let path = path.unwrap_or_default();
+    let waypoints = any_angle.then(|| {
+        map.smooth_path(&path)
+            .into_iter()
+            .map(|(x, y)| [x, y])
+            .collect()
+    });
+    let mut solution = map.clone();
+    solution.mark_path(&path);
+
+    SolveResponse {
+        solution_map: solution.to_string(),
+        path_found,
+        path: path.into_iter().map(PointDto::from).collect(),
+        waypoints,
+        trace,
+        violated_constraint: None,
+    }
+}
+
+// Solves many pairs on one map with a single search per distinct start
+async fn solve_batch(
+    Path(id): Path<Uuid>,
+    State(map_store): State<MapStore>,
+    Json(payload): Json<BatchSolveRequest>,
+) -> Result<Json<ApiResponse<Vec<BatchRouteDto>>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    if !matches!(payload.algorithm, Algorithm::Bfs | Algorithm::Dijkstra) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Batch solve supports only bfs and dijkstra".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    if map.kind == GridKind::Hex && payload.connectivity != Connectivity::Four {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Connectivity 8 is not supported on hex maps".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let mut pairs = Vec::with_capacity(payload.pairs.len());
+    for (index, pair) in payload.pairs.into_iter().enumerate() {
+        let start: Point = pair.start.into();
+        let finish: Point = pair.finish.into();
+        if !map.validate_coordinates(start) || !map.validate_coordinates(finish) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Pair {} has an invalid position", index),
+                    code: None,
+                }),
+            ));
+        }
+        pairs.push((start, finish));
+    }
+
+    let budget = SearchBudget {
+        cancel: Some(CancelToken::default()),
+        ..solve_budget(payload.max_expanded, payload.timeout_ms)
+    };
+    let _cancel_on_drop = budget.cancel.clone().map(CancelOnDrop);
+    let (algorithm, connectivity, parallel) =
+        (payload.algorithm, payload.connectivity, payload.parallel);
+    let routes = tokio::task::spawn_blocking(move || {
+        map.solve_batch(&pairs, algorithm, connectivity, &budget, parallel)
+    })
+    .await
+    .expect("search task panicked")
+    .map_err(budget_exceeded)?;
+
+    Ok(Json(ApiResponse {
+        data: routes
+            .into_iter()
+            .map(|route| match route {
+                Some(route) => BatchRouteDto {
+                    path_found: true,
+                    path: route.path.into_iter().map(PointDto::from).collect(),
+                    cost: Some(route.cost),
+                },
+                None => BatchRouteDto {
+                    path_found: false,
+                    path: Vec::new(),
+                    cost: None,
+                },
+            })
+            .collect(),
+    }))
+}
+
+// Expansions between two progress events of a streamed solve
+const PROGRESS_INTERVAL: usize = 1000;
+
+// Streams `progress` events while the search runs and a final `result`
+// event with the same payload as the plain solve. The search runs on a
+// blocking thread and stops as soon as the client goes away.
+async fn stream_solve(
+    Path(id): Path<Uuid>,
+    Query(params): Query<SolveQuery>,
+    State(map_store): State<MapStore>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = {
+        let maps = map_store.read().await;
+        let stored = maps.get(&id).ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Map not found".to_string(),
+                    code: None,
+                }),
+            )
+        })?;
+        stored.map.clone()
+    };
+
+    let options = SearchOptions {
+        algorithm: params.algorithm,
+        connectivity: params.connectivity,
+    };
+    let any_angle = params.any_angle;
+    let budget = solve_budget(params.max_expanded, params.timeout_ms);
+
+    if params.has_constraints() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Path constraints are not supported when streaming".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let (start, end): (Point, Point) = params.into();
+
+    validate_solve(&map, start, end, options, any_angle)?;
+
+    if !searches_cells(&map, options.algorithm) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Progress streaming is not supported for jps and hpa".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        let mut observer = ProgressObserver {
+            map: &map,
+            finish: end,
+            options,
+            sender: &sender,
+            frontier: HashSet::new(),
+            visited: 0,
+            best_distance: None,
+        };
+        let result = map.search_budgeted(start, end, options, &budget, &mut observer);
+        if observer.sender.is_closed() {
+            return;
+        }
+
+        let progress = observer.progress();
+        // Both sends fail only if the client has already gone
+        let _ = sender.blocking_send(sse_event("progress", &progress));
+        let _ = match result {
+            Ok(result) => {
+                let response = solve_response(&map, result.path, any_angle, None);
+                sender.blocking_send(sse_event("result", &response))
+            }
+            Err(exceeded) => {
+                let (_, Json(error)) = budget_exceeded(exceeded);
+                sender.blocking_send(sse_event("error", &error))
+            }
+        };
+    });
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        let event = receiver.recv().await?;
+        Some((Ok(event), receiver))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn sse_event(name: &str, data: &impl Serialize) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .expect("response DTOs always serialize")
+}
+
+// Counts the search steps and reports them to a streaming client; the
+// receiver is dropped when the client disconnects, which stops the search
+struct ProgressObserver<'a> {
+    map: &'a Map,
+    finish: Point,
+    options: SearchOptions,
+    sender: &'a mpsc::Sender<Event>,
+    frontier: HashSet<Point>,
+    visited: usize,
+    best_distance: Option<u32>,
+}
+
+impl ProgressObserver<'_> {
+    fn progress(&self) -> SolveProgress {
+        SolveProgress {
+            visited: self.visited,
+            frontier: self.frontier.len(),
+            best_distance: self.best_distance,
+        }
+    }
+}
+
+impl SearchObserver<Point> for ProgressObserver<'_> {
+    fn on_push(&mut self, point: Point) {
+        self.frontier.insert(point);
+    }
+
+    fn on_pop(&mut self, point: Point) {
+        self.frontier.remove(&point);
+        self.visited += 1;
+        let distance = self
+            .map
+            .heuristic(point, self.finish, self.options.connectivity);
+        self.best_distance = Some(self.best_distance.map_or(distance, |d| d.min(distance)));
+
+        // A slow client misses intermediate snapshots instead of slowing
+        // the search down
+        if self.visited.is_multiple_of(PROGRESS_INTERVAL) {
+            let _ = self
+                .sender
+                .try_send(sse_event("progress", &self.progress()));
+        }
+    }
+
+    fn keep_going(&self) -> bool {
+        !self.sender.is_closed()
+    }
+}
+
+async fn map_distances(
+    Path(id): Path<Uuid>,
+    Query(params): Query<DistancesQuery>,
+    State(map_store): State<MapStore>,
+) -> Result<Json<ApiResponse<DistancesResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    let source = Point::from_str(&params.from)
+        .ok()
+        .filter(|&point| map.validate_coordinates(point))
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Source position is invalid".to_string(),
+                    code: None,
+                }),
+            )
+        })?;
+
+    // Walks the whole map, so it runs under a budget like the components
+    let data = run_budgeted(params.max_expanded, params.timeout_ms, move |budget| {
+        let distances = map.distances_budgeted(source, budget, &mut NoObserver)?;
+        let heatmap = map.render_heatmap(&distances);
+        Ok(DistancesResponse { distances, heatmap })
+    })
+    .await?;
+
+    Ok(Json(ApiResponse { data }))
+}
+
+async fn distance_matrix(
+    Path(id): Path<Uuid>,
+    Query(params): Query<DistanceMatrixQuery>,
+    State(map_store): State<MapStore>,
+    Json(payload): Json<DistanceMatrixRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    if !matches!(payload.algorithm, Algorithm::Bfs | Algorithm::Dijkstra) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Distance matrix supports only bfs and dijkstra".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    if map.kind == GridKind::Hex && payload.connectivity != Connectivity::Four {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Connectivity 8 is not supported on hex maps".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut names = Vec::with_capacity(payload.points.len());
+    let mut points = Vec::with_capacity(payload.points.len());
+    for named in payload.points {
+        let point = Point {
+            x: named.x,
+            y: named.y,
+        };
+        if !map.validate_coordinates(point) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Point '{}' is invalid", named.name),
+                    code: None,
+                }),
+            ));
+        }
+        if !seen.insert(named.name.clone()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Point name '{}' is used more than once", named.name),
+                    code: None,
+                }),
+            ));
+        }
+        names.push(named.name);
+        points.push(point);
+    }
+
+    let budget = SearchBudget {
+        cancel: Some(CancelToken::default()),
+        ..solve_budget(payload.max_expanded, payload.timeout_ms)
+    };
+    let _cancel_on_drop = budget.cancel.clone().map(CancelOnDrop);
+    let (algorithm, connectivity) = (payload.algorithm, payload.connectivity);
+    let distances = tokio::task::spawn_blocking(move || {
+        map.distance_matrix(&points, algorithm, connectivity, &budget)
+    })
+    .await
+    .expect("search task panicked")
+    .map_err(budget_exceeded)?;
+
+    Ok(match params.format {
+        MatrixFormat::Json => Json(ApiResponse {
+            data: DistanceMatrixResponse { names, distances },
+        })
+        .into_response(),
+        MatrixFormat::Csv => (
+            [(header::CONTENT_TYPE, "text/csv")],
+            distance_matrix_csv(&names, &distances),
+        )
+            .into_response(),
+    })
+}
+
+// Labels every open cell, so it runs under a budget like the metrics
+async fn map_components(
+    Path(id): Path<Uuid>,
+    Query(params): Query<ComponentsQuery>,
+    State(map_store): State<MapStore>,
+) -> Result<Json<ApiResponse<ComponentsResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    let start = resolve_point(
+        &map,
+        params.start.as_deref(),
+        Cell::Start,
+        "Start position  is invalid",
+    )?;
+
+    let components = run_budgeted(params.max_expanded, params.timeout_ms, move |budget| {
+        map.connected_components_budgeted(budget, &mut NoObserver)
+    })
+    .await?;
+    let start_component = start.and_then(|point| components.component_of(point));
+    let unreachable_components = match start_component {
+        Some(start_id) => components
+            .components
+            .iter()
+            .map(|component| component.id)
+            .filter(|&id| id != start_id)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Ok(Json(ApiResponse {
+        data: ComponentsResponse {
+            connected: components.is_connected(),
+            components: components.components,
+            labels: components.labels,
+            start_component,
+            unreachable_components,
+        },
+    }))
+}
+
+// Metrics include the diameter, so they run under a budget like it
+async fn map_metrics(
+    Path(id): Path<Uuid>,
+    Query(params): Query<MetricsQuery>,
+    State(map_store): State<MapStore>,
+) -> Result<Json<ApiResponse<MazeMetrics>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    let start = resolve_point(
+        &map,
+        params.start.as_deref(),
+        Cell::Start,
+        "Start position  is invalid",
+    )?;
+    let finish = resolve_point(
+        &map,
+        params.finish.as_deref(),
+        Cell::End,
+        "End position is invalid",
+    )?;
+    // A lone endpoint from the query would silently leave the solution out;
+    // the map declaring only one of them is fine
+    let explicit = params.start.is_some() || params.finish.is_some();
+    let endpoints = match (start, finish) {
+        (Some(start), Some(finish)) => Some((start, finish)),
+        (None, None) => None,
+        _ if !explicit => None,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: "Start and finish must be given together".to_string(),
+                    code: None,
+                }),
+            ))
+        }
+    };
+
+    let data = run_budgeted(params.max_expanded, params.timeout_ms, move |budget| {
+        metrics::compute_budgeted(&map, endpoints, budget, &mut NoObserver)
+    })
+    .await?;
+
+    Ok(Json(ApiResponse { data }))
+}
+
+// The diameter is quadratic on maps with loops, so it runs under a budget
+async fn map_diameter(
+    Path(id): Path<Uuid>,
+    Query(params): Query<BudgetQuery>,
+    State(map_store): State<MapStore>,
+) -> Result<Json<ApiResponse<DiameterResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+    let diameter = run_budgeted(params.max_expanded, params.timeout_ms, move |budget| {
+        map.diameter_budgeted(budget, &mut NoObserver)
+    })
+    .await?
+    .ok_or_else(no_open_cells)?;
+
+    Ok(Json(ApiResponse {
+        data: diameter.into(),
+    }))
+}
+
+// Writes `i` and `O` markers at the ends of the diameter into the stored map.
+// The diameter is found on a copy without holding the lock; if the map was
+// edited meanwhile, the markers would be stale and the request fails.
+async fn place_endpoints(
+    Path(id): Path<Uuid>,
+    Query(params): Query<BudgetQuery>,
+    State(map_store): State<MapStore>,
+    State(cache): State<SolveCache>,
+) -> Result<Json<ApiResponse<DiameterResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let (map, version) = {
+        let maps = map_store.read().await;
+        let stored = maps.get(&id).ok_or_else(map_not_found)?;
+        (stored.map.clone(), stored.version)
+    };
+    // The marked copy is built and rendered off the lock, so the write lock
+    // below is held only to swap it in
+    let (diameter, marked, map_string) =
+        run_budgeted(params.max_expanded, params.timeout_ms, move |budget| {
+            let diameter = map.diameter_budgeted(budget, &mut NoObserver)?;
+            Ok(diameter.map(|diameter| {
+                let mut marked = Arc::unwrap_or_clone(map);
+                marked.mark_endpoints(diameter);
+                let map_string = marked.to_string();
+                (diameter, marked, map_string)
+            }))
+        })
+        .await?
+        .ok_or_else(no_open_cells)?;
+
+    {
+        let mut maps = map_store.write().await;
+        let stored = maps.get_mut(&id).ok_or_else(map_not_found)?;
+        if stored.version != version {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(ErrorResponse {
+                    error: "Map was modified during the request".to_string(),
+                    code: Some("map_modified"),
+                }),
+            ));
+        }
+
+        stored.map = Arc::new(marked);
+        // Only markers moved, the walls and hence the hierarchy stay the same
+        stored.version += 1;
+    }
+    cache.invalidate_map(id);
+
+    Ok(Json(ApiResponse {
+        data: DiameterResponse {
+            map_string: Some(map_string),
+            ..diameter.into()
+        },
+    }))
+}
+
+fn map_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Map not found".to_string(),
+            code: None,
+        }),
+    )
+}
+
+fn no_open_cells() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(ErrorResponse {
+            error: "Map has no open cells".to_string(),
+            code: None,
+        }),
+    )
+}
+
+// Point from a query parameter ("x,y"); falls back to the cell of the given
+// kind declared in the map itself
+fn resolve_point(
+    map: &Map,
+    param: Option<&str>,
+    declared: Cell,
+    error: &str,
+) -> Result<Option<Point>, (StatusCode, Json<ErrorResponse>)> {
+    match param {
+        Some(param) => Point::from_str(param)
+            .ok()
+            .filter(|&point| map.validate_coordinates(point))
+            .map(Some)
+            .ok_or_else(|| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: error.to_string(),
+                        code: None,
+                    }),
+                )
+            }),
+        None => Ok(map.find_cell(declared)),
+    }
+}
+
+async fn create_session(
+    Path(id): Path<Uuid>,
+    State(map_store): State<MapStore>,
+    State(sessions): State<SessionStore>,
+    Json(payload): Json<CreateSessionRequest>,
+) -> Result<Json<ApiResponse<SessionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = {
+        let maps = map_store.read().await;
+        let stored = maps.get(&id).ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse {
+                    error: "Map not found".to_string(),
+                    code: None,
+                }),
+            )
+        })?;
+        stored.map.clone()
+    };
+
+    let start: Point = payload.start.into();
+    let finish: Point = payload.finish.into();
+
+    if !map.validate_coordinates(start) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Start position  is invalid".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    if !map.validate_coordinates(finish) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "End position is invalid".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let session_id = Uuid::new_v4();
+    let (planner, response) =
+        run_budgeted(payload.max_expanded, payload.timeout_ms, move |budget| {
+            let planner = Planner::new(Arc::unwrap_or_clone(map), start, finish, budget)?;
+            let response = SessionResponse::new(session_id, &planner);
+            Ok((planner, response))
+        })
+        .await?;
+    sessions
+        .insert(session_id, planner)
+        .await
+        .map_err(|CreateError::TooManySessions| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorResponse {
+                    error: "Too many sessions".to_string(),
+                    code: Some("too_many_sessions"),
+                }),
+            )
+        })?;
+
+    Ok(Json(ApiResponse { data: response }))
+}
+
+async fn get_session(
+    Path(id): Path<Uuid>,
+    State(sessions): State<SessionStore>,
+) -> Result<Json<ApiResponse<SessionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let planner = sessions.get(id).await.ok_or_else(session_not_found)?;
+    let data = SessionResponse::new(id, &*planner.lock().await);
+
+    Ok(Json(ApiResponse { data }))
+}
+
+// Paints walls / empty cells and repairs the path incrementally
+async fn update_session_cells(
+    Path(id): Path<Uuid>,
+    State(sessions): State<SessionStore>,
+    Json(payload): Json<UpdateCellsRequest>,
+) -> Result<Json<ApiResponse<SessionResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let planner = sessions.get(id).await.ok_or_else(session_not_found)?;
+    let (max_expanded, timeout_ms) = (payload.max_expanded, payload.timeout_ms);
+    // Every cell is checked before any changes; a repair that runs out of
+    // budget is rolled back by the planner, so the session stays as it was
+    let data = run_budgeted(max_expanded, timeout_ms, move |budget| {
+        let mut planner = planner.blocking_lock();
+        let map = planner.map();
+        let mut changes = Vec::with_capacity(payload.cells.len());
+        for cell in payload.cells {
+            let point = Point {
+                x: cell.x,
+                y: cell.y,
+            };
+            if point.x >= map.rows
+                || point.y >= map.cols
+                || point == planner.start()
+                || point == planner.goal()
+            {
+                return Ok(Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: "Cell change is invalid".to_string(),
+                        code: None,
+                    }),
+                )));
+            }
+            changes.push((point, cell.wall));
+        }
+
+        planner.update_cells(&changes, budget)?;
+        Ok(Ok(SessionResponse::new(id, &planner)))
+    })
+    .await??;
+
+    Ok(Json(ApiResponse { data }))
+}
+
+async fn delete_session(
+    Path(id): Path<Uuid>,
+    State(sessions): State<SessionStore>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if sessions.remove(id).await {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(session_not_found())
+    }
+}
+
+fn session_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Session not found".to_string(),
+            code: None,
+        }),
+    )
+}
+
+// Routes several agents at once without vertex or swap conflicts
+async fn plan_agents(
+    Path(id): Path<Uuid>,
+    State(map_store): State<MapStore>,
+    Json(payload): Json<PlanAgentsRequest>,
+) -> Result<Json<ApiResponse<PlanAgentsResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    let mut agents = Vec::with_capacity(payload.agents.len());
+    for (index, agent) in payload.agents.into_iter().enumerate() {
+        let start: Point = agent.start.into();
+        let goal: Point = agent.finish.into();
+        if !map.validate_coordinates(start) || !map.validate_coordinates(goal) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse {
+                    error: format!("Agent {} has an invalid position", index),
+                    code: None,
+                }),
+            ));
+        }
+        agents.push(Agent { start, goal });
+    }
+
+    // Planning errors other than the budget are reported below
+    let planned = run_budgeted(payload.max_expanded, payload.timeout_ms, move |budget| {
+        Ok(multi_agent::plan(&map, &agents, budget).map(|paths| {
+            let frames = multi_agent::render_frames(&map, &paths);
+            (paths, frames)
+        }))
+    })
+    .await?;
+    let (paths, frames) = planned.map_err(|error| match error {
+        PlanError::SharedEndpoint => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Agents must have distinct starts and goals".to_string(),
+                code: None,
+            }),
+        ),
+        PlanError::TooManyAgents => (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("At most {} agents can be planned", multi_agent::MAX_AGENTS),
+                code: Some("too_many_agents"),
+            }),
+        ),
+        PlanError::Unreachable(index) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                error: format!("Goal of agent {} is unreachable from its start", index),
+                code: Some("unreachable_goal"),
+            }),
+        ),
+        PlanError::NoPath(index) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ErrorResponse {
+                error: format!("No conflict-free path found for agent {}", index),
+                code: None,
+            }),
+        ),
+        PlanError::BudgetExceeded(exceeded) => budget_exceeded(exceeded),
+    })?;
+
+    Ok(Json(ApiResponse {
+        data: PlanAgentsResponse {
+            makespan: frames.len().saturating_sub(1),
+            frames,
+            paths: paths.iter().map(|path| timed(path)).collect(),
+        },
+    }))
+}
+
+// Earliest interception of a target moving along a known trajectory
+async fn pursue_target(
+    Path(id): Path<Uuid>,
+    State(map_store): State<MapStore>,
+    Json(payload): Json<PursuitRequest>,
+) -> Result<Json<ApiResponse<PursuitResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map = stored_map(&map_store, id).await?;
+
+    let pursuer: Point = payload.pursuer.into();
+    let target: Point = payload.target.into();
+    if !map.validate_coordinates(pursuer) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Pursuer position is invalid".to_string(),
+                code: None,
+            }),
+        ));
+    }
+    if !map.validate_coordinates(target) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Target position is invalid".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let policy = match payload.policy {
+        TargetPolicyDto::Route => {
+            TargetPolicy::Route(payload.route.into_iter().map(Point::from).collect())
+        }
+        TargetPolicyDto::Flee => TargetPolicy::Flee,
+    };
+    let response = run_budgeted(payload.max_expanded, payload.timeout_ms, move |budget| {
+        let pursuit = map.pursue(pursuer, target, &policy, budget)?;
+        Ok(pursuit.map(|pursuit| match pursuit.interception {
+            Some(interception) => {
+                let mut solution = Map::clone(&map);
+                solution.mark_path(&interception.path);
+                PursuitResponse {
+                    caught: true,
+                    time: Some(interception.time),
+                    path: timed(&interception.path),
+                    target_path: timed(&interception.target_path),
+                    solution_map: solution.to_string(),
+                }
+            }
+            None => PursuitResponse {
+                caught: false,
+                time: None,
+                path: Vec::new(),
+                target_path: timed(&pursuit.trajectory),
+                solution_map: map.to_string(),
+            },
+        }))
+    })
+    .await?
+    .ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Target route is invalid".to_string(),
+                code: None,
+            }),
+        )
+    })?;
+
+    Ok(Json(ApiResponse { data: response }))
+}
+
+// Solves a multi-level map sent with the request; nothing is stored
+async fn solve_layered(
+    State(limits): State<MapLimits>,
+    Json(payload): Json<LayeredSolveRequest>,
+) -> Result<Json<ApiResponse<SolveResponse>>, (StatusCode, Json<ErrorResponse>)> {
+    let map =
+        LayeredMap::parse_with_limits(&payload.map_string, &limits).map_err(parse_map_error)?;
+
+    let start: Point3 = payload.start.into();
+    let finish: Point3 = payload.finish.into();
+
+    if !map.validate_coordinates(start) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "Start position  is invalid".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    if !map.validate_coordinates(finish) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: "End position is invalid".to_string(),
+                code: None,
+            }),
+        ));
+    }
+
+    let budget = SearchBudget {
+        cancel: Some(CancelToken::default()),
+        ..solve_budget(payload.max_expanded, payload.timeout_ms)
+    };
+    let _cancel_on_drop = budget.cancel.clone().map(CancelOnDrop);
+    let (mut map, path) = tokio::task::spawn_blocking(move || {
+        let path = map.find_path_budgeted(start, finish, &budget);
+        (map, path)
+    })
+    .await
+    .expect("search task panicked");
+    let path = path.map_err(budget_exceeded)?;
+    let path_found = path.is_some();
+    let path = path.unwrap_or_default();
+    map.mark_path(&path);
+
+    Ok(Json(ApiResponse {
+        data: SolveResponse {
+            solution_map: map.to_string(),
             path_found,
+            path: path.into_iter().map(PointDto::from).collect(),
+            waypoints: None,
+            trace: None,
+            violated_constraint: None,
         },
     }))
 }
+
+async fn solve_cache_stats(State(cache): State<SolveCache>) -> Json<ApiResponse<CacheStats>> {
+    Json(ApiResponse {
+        data: cache.stats(),
+    })
+}
+
+// Upper bound on the wall-clock time of a whole bulk request
+const BULK_TIME_LIMIT: Duration = Duration::from_secs(60);
+
+// Processes all stored maps in parallel, one result per map ordered by id
+async fn run_bulk(
+    State(map_store): State<MapStore>,
+    Json(payload): Json<BulkRequest>,
+) -> Json<ApiResponse<Vec<BulkOutcome>>> {
+    let mut items: Vec<BulkItem> = map_store
+        .read()
+        .await
+        .iter()
+        .map(|(id, stored)| BulkItem {
+            name: id.to_string(),
+            input: BulkInput::Map(stored.map.clone(), stored.hierarchy.clone()),
+        })
+        .collect();
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let operation = match payload.operation {
+        BulkOperationKind::Validate => BulkOperation::Validate,
+        BulkOperationKind::Metrics => BulkOperation::Metrics,
+        BulkOperationKind::Solve => BulkOperation::Solve(SearchOptions {
+            algorithm: payload.algorithm,
+            connectivity: payload.connectivity,
+        }),
+    };
+    let budget = BulkBudget {
+        max_expanded: payload.max_expanded,
+        time_limit: Some(payload.timeout_ms.map_or(SOLVE_TIME_LIMIT, |timeout| {
+            Duration::from_millis(timeout).min(SOLVE_TIME_LIMIT)
+        })),
+        deadline: Some(Instant::now() + BULK_TIME_LIMIT),
+        cancel: Some(CancelToken::default()),
+    };
+
+    // Like a single solve, the batch stops if the client goes away
+    let _cancel_on_drop = budget.cancel.clone().map(CancelOnDrop);
+    let outcomes = tokio::task::spawn_blocking(move || {
+        bulk::run(
+            &items,
+            operation,
+            &MapLimits::UNLIMITED,
+            &budget,
+            bulk::default_workers(),
+        )
+    })
+    .await
+    .expect("bulk task panicked");
+
+    Json(ApiResponse { data: outcomes })
+}
+
+async fn create_job(
+    State(map_store): State<MapStore>,
+    State(jobs): State<JobQueue>,
+    State(limits): State<MapLimits>,
+    Json(payload): Json<CreateJobRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<JobView>>), (StatusCode, Json<ErrorResponse>)> {
+    // Everything that can be checked up front is rejected before queueing
+    let (kind, work): (&'static str, crate::jobs::Work) = match payload.job {
+        JobRequest::Solve {
+            map_id,
+            start,
+            finish,
+            algorithm,
+            connectivity,
+        } => {
+            let map = stored_map(&map_store, map_id).await?;
+            let options = SearchOptions {
+                algorithm,
+                connectivity,
+            };
+            let (start, end) = (start.into(), finish.into());
+            validate_solve(&map, start, end, options, false)?;
+
+            if !searches_cells(&map, algorithm) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: "Solve jobs are not supported for jps and hpa".to_string(),
+                        code: None,
+                    }),
+                ));
+            }
+
+            (
+                "solve",
+                Box::new(move |context| {
+                    let budget = context.budget.clone();
+                    let result = map
+                        .search_budgeted(start, end, options, &budget, context)
+                        .map_err(|exceeded| exceeded.to_string())?;
+                    job_result(solve_response(&map, result.path, false, None))
+                }),
+            )
+        }
+        JobRequest::Generate { rows, cols, seed } => {
+            if rows < 2 || cols < 2 || !rows.is_multiple_of(2) || !cols.is_multiple_of(2) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(ErrorResponse {
+                        error: "Maze dimensions must be even and at least 2".to_string(),
+                        code: None,
+                    }),
+                ));
+            }
+            limits.check(rows, cols).map_err(parse_map_error)?;
+
+            let seed = seed.unwrap_or_else(|| Uuid::new_v4().as_u64_pair().0);
+            let map_store = map_store.clone();
+            (
+                "generate",
+                Box::new(move |context| {
+                    let budget = context.budget.clone();
+                    let mut observer = Budgeted::new(&budget, context);
+                    let map = Map::generate_maze(rows, cols, seed, &mut observer);
+                    let map = observer
+                        .finish(map)
+                        .map_err(|exceeded| exceeded.to_string())?;
+                    let id = Uuid::new_v4();
+                    let map_string = map.to_string();
+                    map_store.blocking_write().insert(id, StoredMap::new(map));
+                    job_result(MapResponse { id, map_string })
+                }),
+            )
+        }
+        JobRequest::Analysis { map_id, analysis } => {
+            let map = stored_map(&map_store, map_id).await?;
+            (
+                "analysis",
+                Box::new(move |context| {
+                    let budget = context.budget.clone();
+                    match analysis {
+                        AnalysisKind::Metrics => {
+                            let metrics = metrics::compute_budgeted(&map, None, &budget, context)
+                                .map_err(|exceeded| exceeded.to_string())?;
+                            job_result(metrics)
+                        }
+                        AnalysisKind::Diameter => {
+                            let diameter = map
+                                .diameter_budgeted(&budget, context)
+                                .map_err(|exceeded| exceeded.to_string())?
+                                .ok_or("Map has no open cells")?;
+                            job_result(DiameterResponse::from(diameter))
+                        }
+                    }
+                }),
+            )
+        }
+    };
+
+    let time_limit = payload.time_limit_ms.map(Duration::from_millis);
+    let id = jobs
+        .submit(kind, payload.max_steps, time_limit, work)
+        .map_err(|SubmitError::QueueFull| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(ErrorResponse {
+                    error: "Job queue is full".to_string(),
+                    code: None,
+                }),
+            )
+        })?;
+    let job = jobs.get(id).ok_or_else(job_not_found)?;
+
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse { data: job })))
+}
+
+async fn get_job(
+    Path(id): Path<Uuid>,
+    State(jobs): State<JobQueue>,
+) -> Result<Json<ApiResponse<JobView>>, (StatusCode, Json<ErrorResponse>)> {
+    let job = jobs.get(id).ok_or_else(job_not_found)?;
+
+    Ok(Json(ApiResponse { data: job }))
+}
+
+async fn cancel_job(
+    Path(id): Path<Uuid>,
+    State(jobs): State<JobQueue>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if jobs.cancel(id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(job_not_found())
+    }
+}
+
+fn job_not_found() -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            error: "Job not found".to_string(),
+            code: None,
+        }),
+    )
+}
+
+// A copy of a stored map for work that outlives the request
+async fn stored_map(
+    map_store: &MapStore,
+    id: Uuid,
+) -> Result<Arc<Map>, (StatusCode, Json<ErrorResponse>)> {
+    let maps = map_store.read().await;
+    let stored = maps.get(&id).ok_or_else(map_not_found)?;
+    Ok(stored.map.clone())
+}
+
+fn job_result(data: impl Serialize) -> Result<serde_json::Value, String> {
+    serde_json::to_value(data).map_err(|error| error.to_string())
+}
+
+// Attaches time steps to a sequence of positions
+fn timed(path: &[Point]) -> Vec<TimedPointDto> {
+    path.iter()
+        .enumerate()
+        .map(|(t, point)| TimedPointDto {
+            x: point.x,
+            y: point.y,
+            t,
+        })
+        .collect()
+}
+
+// Parses and validates the map from a create/update request. Both steps
+// are linear in the map size, so they run off the async workers under the
+// default solve budget.
+async fn parse_map(
+    payload: &CreateMapRequest,
+    limits: &MapLimits,
+) -> Result<Map, (StatusCode, Json<ErrorResponse>)> {
+    let map_string = payload.map_string.clone();
+    let require_connected = payload.require_connected;
+    let limits = *limits;
+
+    run_budgeted(None, None, move |budget| {
+        let map = match Map::parse_with_limits(&map_string, &limits) {
+            Ok(map) => map,
+            Err(error) => return Ok(Err(parse_map_error(error))),
+        };
+        if require_connected
+            && !map
+                .connected_components_budgeted(budget, &mut NoObserver)?
+                .is_connected()
+        {
+            return Ok(Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ErrorResponse {
+                    error: "Map is not fully connected".to_string(),
+                    code: None,
+                }),
+            )));
+        }
+        Ok(Ok(map))
+    })
+    .await?
+}
+
+fn parse_map_error(error: ParseMapError) -> (StatusCode, Json<ErrorResponse>) {
+    let (status, code) = match error {
+        ParseMapError::Format => (StatusCode::BAD_REQUEST, None),
+        ParseMapError::TooManyRows(_) => (StatusCode::UNPROCESSABLE_ENTITY, Some("too_many_rows")),
+        ParseMapError::TooManyCols(_) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, Some("too_many_columns"))
+        }
+        ParseMapError::TooManyCells(_) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, Some("too_many_cells"))
+        }
+    };
+
+    (
+        status,
+        Json(ErrorResponse {
+            error: error.to_string(),
+            code,
+        }),
+    )
+}
+
+// The body limit rejects requests before any handler runs, with a plain
+// text body; give it the same shape as the other errors
+fn body_limit_error(response: Response, limit: usize) -> Response {
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return response;
+    }
+
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        Json(ErrorResponse {
+            error: format!("Request body is larger than {} bytes", limit),
+            code: Some("body_too_large"),
+        }),
+    )
+        .into_response()
+}
@@ -0,0 +1,264 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::graph::{BudgetExceeded, CancelToken, NoObserver, SearchBudget};
+use crate::hpa::Hierarchy;
+use crate::map::{Algorithm, Cell, Connectivity, GridKind, Map, SearchOptions};
+use crate::map_parser::MapLimits;
+use crate::metrics;
+
+/// Операция, применяемая к каждой карте
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkOperation {
+    /// Разбор, размеры, связность и объявленные старт и финиш
+    Validate,
+    Metrics,
+    /// Поиск между объявленными стартом и финишем
+    Solve(SearchOptions),
+}
+
+/// Пределы метрик и поиска на каждую карту; проверка линейна по размеру
+/// карты и их не учитывает. Срок и отмена всего пакета действуют на все
+/// операции: карты, до которых дошла очередь после них, получают ошибку.
+#[derive(Debug, Clone, Default)]
+pub struct BulkBudget {
+    pub max_expanded: Option<usize>,
+    pub time_limit: Option<Duration>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl BulkBudget {
+    // Время отсчитывается от начала работы над картой, а не над всем
+    // пакетом, но не дальше срока пакета
+    fn start(&self) -> SearchBudget {
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        SearchBudget {
+            max_expanded: self.max_expanded,
+            deadline: match (deadline, self.deadline) {
+                (Some(own), Some(batch)) => Some(own.min(batch)),
+                (own, batch) => own.or(batch),
+            },
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    // Не истёк ли срок пакета и не отменён ли он
+    fn check_batch(&self) -> Result<(), BudgetExceeded> {
+        SearchBudget {
+            deadline: self.deadline,
+            cancel: self.cancel.clone(),
+            ..SearchBudget::default()
+        }
+        .check_deadline()
+    }
+}
+
+/// Карта для обработки: текст ещё разбирается в пуле, а готовая карта
+/// разделяется с хранилищем без копирования - вместе с её абстракцией HPA*
+pub enum BulkInput {
+    Text(String),
+    Map(Arc<Map>, Arc<OnceLock<Hierarchy>>),
+    /// Карту не удалось прочитать; причина попадает в итог
+    Unreadable(String),
+}
+
+pub struct BulkItem {
+    pub name: String,
+    pub input: BulkInput,
+}
+
+/// Итог по одной карте: результат или ошибка
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkOutcome {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub rows: usize,
+    pub cols: usize,
+    pub open_cells: usize,
+    pub components: usize,
+    pub has_endpoints: bool,
+    /// Лежат ли объявленные старт и финиш в одной компоненте
+    pub endpoints_connected: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SolveReport {
+    pub path_found: bool,
+    /// Число ходов найденного пути
+    pub length: Option<usize>,
+    pub expanded: usize,
+}
+
+/// Обрабатывает карты в пуле из `workers` потоков; итоги идут в порядке
+/// карт. Каждая карта обрабатывается отдельно: её ошибка или паника
+/// становится ошибкой только этой карты.
+pub fn run(
+    items: &[BulkItem],
+    operation: BulkOperation,
+    limits: &MapLimits,
+    budget: &BulkBudget,
+    workers: usize,
+) -> Vec<BulkOutcome> {
+    let outcomes = parallel_map(items, workers, |item| {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            process(item, operation, limits, budget)
+        }))
+        .unwrap_or_else(|_| Err("Processing failed unexpectedly".to_string()))
+    });
+
+    items
+        .iter()
+        .zip(outcomes)
+        .map(|(item, outcome)| {
+            let (result, error) = match outcome {
+                Ok(result) => (Some(result), None),
+                Err(error) => (None, Some(error)),
+            };
+            BulkOutcome {
+                name: item.name.clone(),
+                result,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Число потоков по умолчанию - по числу ядер
+pub fn default_workers() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Потоки берут следующий элемент из общего счётчика, поэтому долгие
+// карты не задерживают остальные
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    workers: usize,
+    work: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let mut done: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.clamp(1, items.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        done.push((index, work(item)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("bulk worker panicked"))
+            .collect()
+    });
+
+    done.sort_by_key(|&(index, _)| index);
+    done.into_iter().map(|(_, result)| result).collect()
+}
+
+fn process(
+    item: &BulkItem,
+    operation: BulkOperation,
+    limits: &MapLimits,
+    budget: &BulkBudget,
+) -> Result<Value, String> {
+    budget
+        .check_batch()
+        .map_err(|exceeded| exceeded.to_string())?;
+    let parsed;
+    let fresh = OnceLock::new();
+    let (map, hierarchy) = match &item.input {
+        BulkInput::Text(text) => {
+            parsed = Map::parse_with_limits(text, limits).map_err(|error| error.to_string())?;
+            (&parsed, &fresh)
+        }
+        BulkInput::Map(map, hierarchy) => (map.as_ref(), hierarchy.as_ref()),
+        BulkInput::Unreadable(error) => return Err(error.clone()),
+    };
+
+    let result = match operation {
+        BulkOperation::Validate => serde_json::to_value(validate(map)),
+        BulkOperation::Metrics => {
+            let endpoints = map.find_cell(Cell::Start).zip(map.find_cell(Cell::End));
+            let metrics =
+                metrics::compute_budgeted(map, endpoints, &budget.start(), &mut NoObserver)
+                    .map_err(|exceeded| exceeded.to_string())?;
+            serde_json::to_value(metrics)
+        }
+        BulkOperation::Solve(options) => {
+            serde_json::to_value(solve(map, hierarchy, options, budget)?)
+        }
+    };
+    result.map_err(|error| error.to_string())
+}
+
+fn validate(map: &Map) -> ValidationReport {
+    let components = map.connected_components();
+    let start = map.find_cell(Cell::Start);
+    let finish = map.find_cell(Cell::End);
+
+    ValidationReport {
+        rows: map.rows,
+        cols: map.cols,
+        open_cells: components.components.iter().map(|c| c.size).sum(),
+        components: components.components.len(),
+        has_endpoints: start.is_some() && finish.is_some(),
+        endpoints_connected: start.zip(finish).map(|(start, finish)| {
+            components.component_of(start) == components.component_of(finish)
+        }),
+    }
+}
+
+fn solve(
+    map: &Map,
+    hierarchy: &OnceLock<Hierarchy>,
+    options: SearchOptions,
+    budget: &BulkBudget,
+) -> Result<SolveReport, String> {
+    let (Some(start), Some(finish)) = (map.find_cell(Cell::Start), map.find_cell(Cell::End)) else {
+        return Err("Map has no declared start and end".to_string());
+    };
+    // Те же ограничения, что и у одиночного поиска через API
+    if options.algorithm == Algorithm::Hpa && options.connectivity != Connectivity::Four {
+        return Err("HPA* supports only 4-connected maps".to_string());
+    }
+    if map.kind == GridKind::Hex && options.connectivity != Connectivity::Four {
+        return Err("Connectivity 8 is not supported on hex maps".to_string());
+    }
+
+    let result = map
+        .search_cached(
+            start,
+            finish,
+            options,
+            hierarchy,
+            &budget.start(),
+            &mut NoObserver,
+        )
+        .map_err(|exceeded| exceeded.to_string())?;
+
+    Ok(SolveReport {
+        path_found: result.path.is_some(),
+        length: result.path.map(|path| path.len() - 1),
+        expanded: result.expanded,
+    })
+}
@@ -0,0 +1,111 @@
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use crate::bulk::{self, BulkBudget, BulkInput, BulkItem, BulkOperation};
+use crate::map::{Algorithm, Connectivity, SearchOptions};
+use crate::map_parser::MapLimits;
+
+const USAGE: &str = "Usage: lab1 bulk <validate|metrics|solve> <DIR> [--algorithm NAME] \
+[--connectivity 4|8] [--max-expanded N] [--timeout-ms N] [--workers N]";
+
+// Runs a command given on the command line. Exits with 1 if any map
+// failed and with 2 on a usage error.
+pub fn run(args: &[String]) -> ExitCode {
+    match parse_and_run(args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn parse_and_run(args: &[String]) -> Result<bool, String> {
+    let [command, operation, dir, options @ ..] = args else {
+        return Err("Missing arguments".to_string());
+    };
+    if command != "bulk" {
+        return Err(format!("Unknown command '{}'", command));
+    }
+
+    let mut search = SearchOptions::default();
+    let mut budget = BulkBudget::default();
+    let mut workers = bulk::default_workers();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", option))?;
+        match option.as_str() {
+            "--algorithm" => search.algorithm = parse_name::<Algorithm>(value)?,
+            "--connectivity" => search.connectivity = parse_name::<Connectivity>(value)?,
+            "--max-expanded" => budget.max_expanded = Some(parse_number(option, value)?),
+            "--timeout-ms" => {
+                budget.time_limit = Some(Duration::from_millis(parse_number(option, value)? as u64))
+            }
+            "--workers" => workers = parse_number(option, value)?,
+            _ => return Err(format!("Unknown option '{}'", option)),
+        }
+    }
+
+    let operation = match operation.as_str() {
+        "validate" => BulkOperation::Validate,
+        "metrics" => BulkOperation::Metrics,
+        "solve" => BulkOperation::Solve(search),
+        _ => return Err(format!("Unknown operation '{}'", operation)),
+    };
+
+    let items = read_maps(Path::new(dir))?;
+    let outcomes = bulk::run(&items, operation, &MapLimits::UNLIMITED, &budget, workers);
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.error.is_some())
+        .count();
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&outcomes).expect("outcomes serialize to JSON")
+    );
+    eprintln!("{} maps processed, {} failed", outcomes.len(), failed);
+    Ok(failed == 0)
+}
+
+// Every regular file in the directory, sorted by name. A file that cannot
+// be read is still processed so that it shows up as failed.
+fn read_maps(dir: &Path) -> Result<Vec<BulkItem>, String> {
+    let entries = fs::read_dir(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .map(|path| BulkItem {
+            name: path
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+            input: fs::read_to_string(&path).map_or_else(
+                |error| BulkInput::Unreadable(error.to_string()),
+                BulkInput::Text,
+            ),
+        })
+        .collect())
+}
+
+// Algorithm and connectivity use the same names as the API
+fn parse_name<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("Unknown value '{}'", value))
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got '{}'", option, value))
+}
@@ -0,0 +1,527 @@
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Граф, по которому ищут путь обобщённые алгоритмы этого модуля.
+/// Вершины пронумерованы от 0 до `node_count`, так что буферы поиска -
+/// плоские массивы; цены рёбер неотрицательны.
+pub trait Graph {
+    type Node: Copy + Eq;
+
+    fn node_count(&self) -> usize;
+
+    /// Номер вершины в диапазоне `0..node_count()`
+    fn index(&self, node: Self::Node) -> usize;
+
+    /// Вершина по номеру - обратное к `index`
+    fn node(&self, index: usize) -> Self::Node;
+
+    /// Соседи вершины вместе с ценой перехода
+    fn neighbors(&self, node: Self::Node) -> Vec<(Self::Node, u32)>;
+
+    /// Допустимая оценка цены пути (не больше настоящей); без неё A*
+    /// работает как алгоритм Дейкстры
+    fn heuristic(&self, _from: Self::Node, _to: Self::Node) -> u32 {
+        0
+    }
+}
+
+/// Наблюдатель за ходом поиска. Методы по умолчанию пусты, а алгоритмы
+/// обобщены по наблюдателю, так что с `NoObserver` вызовы исчезают при
+/// компиляции и поиск без наблюдения ничего не теряет.
+pub trait SearchObserver<N> {
+    /// Вершина добавлена во фронт (очередь или кучу)
+    fn on_push(&mut self, _node: N) {}
+
+    /// Вершина извлечена из фронта и раскрыта
+    fn on_pop(&mut self, _node: N) {}
+
+    /// Спрашивается после каждого раскрытия; `false` прерывает поиск,
+    /// и его результат остаётся неполным
+    fn keep_going(&self) -> bool {
+        true
+    }
+}
+
+/// Наблюдатель, который ничего не делает
+pub struct NoObserver;
+
+impl<N> SearchObserver<N> for NoObserver {}
+
+/// Событие поиска в порядке появления
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent<N> {
+    Push(N),
+    Pop(N),
+}
+
+/// Наблюдатель, записывающий события поиска. Записывает не больше
+/// `limit` событий: если какое-то пришлось отбросить, запись неполна
+/// (`truncated`) и поиск останавливается
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecorder<N> {
+    pub events: Vec<TraceEvent<N>>,
+    pub truncated: bool,
+    limit: usize,
+}
+
+impl<N> Default for TraceRecorder<N> {
+    fn default() -> Self {
+        Self::with_limit(usize::MAX)
+    }
+}
+
+impl<N> TraceRecorder<N> {
+    pub fn with_limit(limit: usize) -> Self {
+        TraceRecorder {
+            events: Vec::new(),
+            truncated: false,
+            limit,
+        }
+    }
+
+    fn record(&mut self, event: TraceEvent<N>) {
+        if self.events.len() < self.limit {
+            self.events.push(event);
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+impl<N> SearchObserver<N> for TraceRecorder<N> {
+    fn on_push(&mut self, node: N) {
+        self.record(TraceEvent::Push(node));
+    }
+
+    fn on_pop(&mut self, node: N) {
+        self.record(TraceEvent::Pop(node));
+    }
+
+    fn keep_going(&self) -> bool {
+        !self.truncated
+    }
+}
+
+// Часы и флаг отмены опрашиваются раз в столько раскрытий
+const BUDGET_CHECK_INTERVAL: usize = 256;
+
+/// Флаг отмены, общий для того, кто ждёт результат, и потока поиска
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Предел, на котором остановился поиск
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    Expanded,
+    Deadline,
+    Cancelled,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            BudgetExceeded::Expanded => "Expanded node limit reached",
+            BudgetExceeded::Deadline => "Time limit reached",
+            BudgetExceeded::Cancelled => "Search was cancelled",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Кооперативные пределы поиска; по умолчанию их нет
+#[derive(Debug, Clone, Default)]
+pub struct SearchBudget {
+    pub max_expanded: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl SearchBudget {
+    fn check(&self, expanded: usize) -> Result<(), BudgetExceeded> {
+        if self.max_expanded.is_some_and(|max| expanded >= max) {
+            return Err(BudgetExceeded::Expanded);
+        }
+        // Первое раскрытие и далее каждое `BUDGET_CHECK_INTERVAL`-е
+        if expanded % BUDGET_CHECK_INTERVAL != 1 {
+            return Ok(());
+        }
+        self.check_deadline()
+    }
+
+    /// Отмена и срок без учёта раскрытий - для работы, которая не
+    /// раскрывает вершины поиска (например, сборки абстракции HPA*)
+    pub fn check_deadline(&self) -> Result<(), BudgetExceeded> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(BudgetExceeded::Cancelled);
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(BudgetExceeded::Deadline);
+        }
+        Ok(())
+    }
+}
+
+/// Обёртка над наблюдателем, которая останавливает поиск по бюджету.
+/// Предел считается превышенным, только если поиск действительно
+/// прервался: раскрытый последним финиш - ещё успех. Остановка по воле
+/// внутреннего наблюдателя превышением не считается.
+pub struct Budgeted<'a, O> {
+    budget: &'a SearchBudget,
+    inner: &'a mut O,
+    expanded: usize,
+    exceeded: Cell<Option<BudgetExceeded>>,
+}
+
+impl<'a, O> Budgeted<'a, O> {
+    pub fn new(budget: &'a SearchBudget, inner: &'a mut O) -> Self {
+        Budgeted {
+            budget,
+            inner,
+            expanded: 0,
+            exceeded: Cell::new(None),
+        }
+    }
+
+    /// Результат поиска или предел, на котором он прервался
+    pub fn finish<T>(self, result: T) -> Result<T, BudgetExceeded> {
+        match self.exceeded.get() {
+            Some(exceeded) => Err(exceeded),
+            None => Ok(result),
+        }
+    }
+}
+
+impl<N, O: SearchObserver<N>> SearchObserver<N> for Budgeted<'_, O> {
+    fn on_push(&mut self, node: N) {
+        self.inner.on_push(node);
+    }
+
+    fn on_pop(&mut self, node: N) {
+        self.expanded += 1;
+        self.inner.on_pop(node);
+    }
+
+    fn keep_going(&self) -> bool {
+        // Сначала внутренний наблюдатель: если на этом раскрытии его цель
+        // уже достигнута, бюджет на нём ещё не превышен
+        if !self.inner.keep_going() {
+            return false;
+        }
+        if let Err(exceeded) = self.budget.check(self.expanded) {
+            self.exceeded.set(Some(exceeded));
+            return false;
+        }
+        true
+    }
+}
+
+// Нет родителя: корень поиска или недостигнутая вершина
+const NO_PARENT: u32 = u32::MAX;
+
+/// Дерево поиска из одной вершины; массивы индексируются номерами вершин
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchTree {
+    /// Число ходов (BFS) или цена пути (Дейкстра, A*); `None` - вершина
+    /// не достигнута
+    pub distances: Vec<Option<u32>>,
+    /// Номер родителя в дереве - четыре байта на вершину
+    parents: Vec<u32>,
+    /// Сколько вершин было извлечено из очереди
+    pub expanded: usize,
+}
+
+impl SearchTree {
+    /// Путь от корня поиска до `node`, если она достигнута
+    pub fn path_to<G: Graph>(&self, graph: &G, node: G::Node) -> Option<Vec<G::Node>> {
+        self.distances[graph.index(node)]?;
+        let mut path = trace_back(graph, &self.parents, node);
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Обход в ширину от `start` без учёта цен рёбер; останавливается, как
+/// только из очереди извлечена `finish` (если она задана)
+pub fn bfs<G: Graph>(graph: &G, start: G::Node, finish: Option<G::Node>) -> SearchTree {
+    bfs_observed(graph, start, finish, &mut NoObserver)
+}
+
+/// `bfs`, сообщающий наблюдателю о каждом шаге
+pub fn bfs_observed<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: Option<G::Node>,
+    observer: &mut impl SearchObserver<G::Node>,
+) -> SearchTree {
+    bfs_bounded_observed(graph, start, finish, None, observer)
+}
+
+/// `bfs_observed`, не заходящий дальше `max_depth` ходов от `start`:
+/// вершины на этой глубине достигаются, но не раскрываются
+pub fn bfs_bounded_observed<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: Option<G::Node>,
+    max_depth: Option<u32>,
+    observer: &mut impl SearchObserver<G::Node>,
+) -> SearchTree {
+    let mut distances = vec![None; graph.node_count()];
+    let mut parents = vec![NO_PARENT; graph.node_count()];
+    let mut queue = VecDeque::from([start]);
+    let mut expanded = 0;
+    distances[graph.index(start)] = Some(0);
+    observer.on_push(start);
+
+    while let Some(current) = queue.pop_front() {
+        expanded += 1;
+        observer.on_pop(current);
+        if Some(current) == finish || !observer.keep_going() {
+            break;
+        }
+
+        let distance = distances[graph.index(current)].unwrap_or(0);
+        if max_depth.is_some_and(|max_depth| distance >= max_depth) {
+            continue;
+        }
+        for (neighbor, _) in graph.neighbors(current) {
+            let index = graph.index(neighbor);
+            if distances[index].is_none() {
+                distances[index] = Some(distance + 1);
+                parents[index] = graph.index(current) as u32;
+                queue.push_back(neighbor);
+                observer.on_push(neighbor);
+            }
+        }
+    }
+
+    SearchTree {
+        distances,
+        parents,
+        expanded,
+    }
+}
+
+/// Встречный обход в ширину для неориентированных графов: на каждом шаге
+/// целиком раскрывается слой той стороны, у которой фронт меньше. Если слой
+/// задел вершины другой стороны, лучший стык выбирается после раскрытия
+/// всего слоя - так путь остаётся кратчайшим по числу ходов.
+/// Возвращает путь и число раскрытых вершин.
+#[allow(dead_code)] // используется в тестах
+pub fn bidirectional_bfs<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: G::Node,
+) -> (Option<Vec<G::Node>>, usize) {
+    bidirectional_bfs_observed(graph, start, finish, &mut NoObserver)
+}
+
+/// `bidirectional_bfs`, сообщающий наблюдателю о каждом шаге обеих сторон
+pub fn bidirectional_bfs_observed<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: G::Node,
+    observer: &mut impl SearchObserver<G::Node>,
+) -> (Option<Vec<G::Node>>, usize) {
+    if start == finish {
+        return (Some(vec![start]), 0);
+    }
+
+    let mut distances = [
+        vec![None; graph.node_count()],
+        vec![None; graph.node_count()],
+    ];
+    let mut parents = [
+        vec![NO_PARENT; graph.node_count()],
+        vec![NO_PARENT; graph.node_count()],
+    ];
+    let mut frontiers = [vec![start], vec![finish]];
+    let mut expanded = 0;
+
+    distances[0][graph.index(start)] = Some(0);
+    distances[1][graph.index(finish)] = Some(0);
+    observer.on_push(start);
+    observer.on_push(finish);
+
+    while !frontiers[0].is_empty() && !frontiers[1].is_empty() {
+        let side = if frontiers[0].len() <= frontiers[1].len() {
+            0
+        } else {
+            1
+        };
+        let other = 1 - side;
+        let mut next = Vec::new();
+        // (длина пути, вершина своей стороны, вершина другой стороны)
+        let mut meeting: Option<(usize, G::Node, G::Node)> = None;
+
+        for &current in &frontiers[side] {
+            // Спрашиваем перед следующим раскрытием: стык, найденный
+            // последним разрешённым слоем, ещё должен попасть в ответ
+            if expanded > 0 && !observer.keep_going() {
+                return (None, expanded);
+            }
+            expanded += 1;
+            observer.on_pop(current);
+            let distance = distances[side][graph.index(current)].unwrap_or(0);
+
+            for (neighbor, _) in graph.neighbors(current) {
+                let index = graph.index(neighbor);
+                if let Some(other_distance) = distances[other][index] {
+                    let length = distance + 1 + other_distance;
+                    if meeting.is_none_or(|(best, _, _)| length < best) {
+                        meeting = Some((length, current, neighbor));
+                    }
+                }
+                if distances[side][index].is_none() {
+                    distances[side][index] = Some(distance + 1);
+                    parents[side][index] = graph.index(current) as u32;
+                    next.push(neighbor);
+                    observer.on_push(neighbor);
+                }
+            }
+        }
+
+        if let Some((_, own, theirs)) = meeting {
+            // Сшиваем половины: от старта до стыка и от стыка до финиша
+            let (forward, backward) = if side == 0 {
+                (own, theirs)
+            } else {
+                (theirs, own)
+            };
+            let mut path = trace_back(graph, &parents[0], forward);
+            path.reverse();
+            path.extend(trace_back(graph, &parents[1], backward));
+            return (Some(path), expanded);
+        }
+
+        frontiers[side] = next;
+    }
+
+    (None, expanded)
+}
+
+/// Алгоритм Дейкстры: цены путей от `start` до всех вершин (или до
+/// извлечения `finish`, если она задана)
+#[allow(dead_code)] // используется в тестах
+pub fn dijkstra<G: Graph>(graph: &G, start: G::Node, finish: Option<G::Node>) -> SearchTree {
+    best_first(graph, start, finish, false, &mut NoObserver)
+}
+
+/// `dijkstra`, сообщающий наблюдателю о каждом шаге
+pub fn dijkstra_observed<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: Option<G::Node>,
+    observer: &mut impl SearchObserver<G::Node>,
+) -> SearchTree {
+    best_first(graph, start, finish, false, observer)
+}
+
+/// A* с эвристикой графа; путь минимальной цены и число раскрытых вершин
+#[allow(dead_code)] // используется в тестах
+pub fn astar<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: G::Node,
+) -> (Option<Vec<G::Node>>, usize) {
+    astar_observed(graph, start, finish, &mut NoObserver)
+}
+
+/// `astar`, сообщающий наблюдателю о каждом шаге
+pub fn astar_observed<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: G::Node,
+    observer: &mut impl SearchObserver<G::Node>,
+) -> (Option<Vec<G::Node>>, usize) {
+    let tree = best_first(graph, start, Some(finish), true, observer);
+    let path = tree.path_to(graph, finish);
+    (path, tree.expanded)
+}
+
+// Поиск по наименьшей оценке: A* или, без эвристики, Дейкстра
+fn best_first<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    finish: Option<G::Node>,
+    use_heuristic: bool,
+    observer: &mut impl SearchObserver<G::Node>,
+) -> SearchTree {
+    let estimate = |node: G::Node| match finish {
+        Some(finish) if use_heuristic => graph.heuristic(node, finish),
+        _ => 0,
+    };
+    let mut distances = vec![None; graph.node_count()];
+    let mut parents = vec![NO_PARENT; graph.node_count()];
+    let mut closed = vec![false; graph.node_count()];
+    let mut heap = BinaryHeap::new();
+    let mut expanded = 0;
+
+    distances[graph.index(start)] = Some(0);
+    heap.push(Reverse((estimate(start), 0, graph.index(start))));
+    observer.on_push(start);
+
+    while let Some(Reverse((_, cost, index))) = heap.pop() {
+        if closed[index] {
+            continue;
+        }
+        closed[index] = true;
+        expanded += 1;
+
+        let current = graph.node(index);
+        observer.on_pop(current);
+        if Some(current) == finish || !observer.keep_going() {
+            break;
+        }
+
+        for (neighbor, step) in graph.neighbors(current) {
+            let new_cost = cost + step;
+            let neighbor_index = graph.index(neighbor);
+            if distances[neighbor_index].is_none_or(|old| new_cost < old) {
+                distances[neighbor_index] = Some(new_cost);
+                parents[neighbor_index] = index as u32;
+                heap.push(Reverse((
+                    new_cost + estimate(neighbor),
+                    new_cost,
+                    neighbor_index,
+                )));
+                observer.on_push(neighbor);
+            }
+        }
+    }
+
+    SearchTree {
+        distances,
+        parents,
+        expanded,
+    }
+}
+
+// Цепочка от `node` по родителям до корня обхода (включительно)
+fn trace_back<G: Graph>(graph: &G, parents: &[u32], node: G::Node) -> Vec<G::Node> {
+    let mut path = vec![node];
+    let mut current = graph.index(node);
+
+    while parents[current] != NO_PARENT {
+        current = parents[current] as usize;
+        path.push(graph.node(current));
+    }
+    path
+}
@@ -11,7 +11,32 @@ mod handler_tests {
     use tower::util::ServiceExt; // for `oneshot` - исправлен импорт
     use uuid::Uuid;
 
-    use crate::api::{create_api_router, MapStore};
+    use crate::api::{
+        create_api_router, create_api_router_with_config, ApiConfig, BodyLimit, MapStore,
+    };
+    use crate::map_parser::MapLimits;
+    use crate::multi_agent;
+    use crate::sessions::SessionConfig;
+    use std::time::Duration;
+
+    // Creates a map through the API and returns its ID
+    async fn create_test_map(app: &axum::Router, map_string: &str) -> String {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/maps")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "map_string": map_string }).to_string()))
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body["data"]["id"].as_str().unwrap().to_string()
+    }
 
     #[tokio::test]
     async fn test_create_map_success() {
@@ -31,7 +56,9 @@ mod handler_tests {
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert!(body["data"]["id"].is_string());
@@ -56,7 +83,9 @@ mod handler_tests {
 
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(body["error"], "Invalid map format");
@@ -77,7 +106,9 @@ mod handler_tests {
 
         assert_eq!(response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert!(body["data"].is_array());
@@ -112,7 +143,9 @@ mod handler_tests {
 
         assert_eq!(list_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(list_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(list_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert!(body["data"].is_array());
@@ -138,7 +171,9 @@ mod handler_tests {
         let create_response = app.clone().oneshot(create_request).await.unwrap();
         assert_eq!(create_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
         let map_id = body["data"]["id"].as_str().unwrap();
 
@@ -153,7 +188,9 @@ mod handler_tests {
 
         assert_eq!(get_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(get_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(get_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(body["data"]["id"], map_id);
@@ -177,7 +214,9 @@ mod handler_tests {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(body["error"], "Map not found");
@@ -200,7 +239,9 @@ mod handler_tests {
         let create_response = app.clone().oneshot(create_request).await.unwrap();
         assert_eq!(create_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
         let map_id = body["data"]["id"].as_str().unwrap();
 
@@ -244,7 +285,9 @@ mod handler_tests {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(body["error"], "Map not found");
@@ -267,14 +310,19 @@ mod handler_tests {
         let create_response = app.clone().oneshot(create_request).await.unwrap();
         assert_eq!(create_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
         let map_id = body["data"]["id"].as_str().unwrap();
 
         // Then solve the map
         let solve_request = Request::builder()
             .method("GET")
-            .uri(format!("/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=2&finish_y=2", map_id))
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=2&finish_y=2",
+                map_id
+            ))
             .body(Body::empty())
             .unwrap();
 
@@ -282,13 +330,15 @@ mod handler_tests {
 
         assert_eq!(solve_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(solve_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(solve_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert!(body["data"]["path_found"].as_bool().unwrap());
         assert!(body["data"]["solution_map"].is_string());
     }
-    
+
     #[tokio::test]
     async fn test_solve_map_not_found() {
         let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
@@ -298,7 +348,10 @@ mod handler_tests {
 
         let request = Request::builder()
             .method("GET")
-            .uri(format!("/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=1&finish_y=1", non_existent_id))
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=1&finish_y=1",
+                non_existent_id
+            ))
             .body(Body::empty())
             .unwrap();
 
@@ -306,7 +359,9 @@ mod handler_tests {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(body["error"], "Map not found");
@@ -329,14 +384,19 @@ mod handler_tests {
         let create_response = app.clone().oneshot(create_request).await.unwrap();
         assert_eq!(create_response.status(), StatusCode::OK);
 
-        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(create_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
         let map_id = body["data"]["id"].as_str().unwrap();
 
         // Try to solve with invalid start position (on wall)
         let solve_request = Request::builder()
             .method("GET")
-            .uri(format!("/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=2&finish_y=2", map_id))
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=2&finish_y=2",
+                map_id
+            ))
             .body(Body::empty())
             .unwrap();
 
@@ -344,9 +404,1798 @@ mod handler_tests {
 
         assert_eq!(solve_response.status(), StatusCode::BAD_REQUEST);
 
-        let body = axum::body::to_bytes(solve_response.into_body(), usize::MAX).await.unwrap(); // исправлен импорт
+        let body = axum::body::to_bytes(solve_response.into_body(), usize::MAX)
+            .await
+            .unwrap(); // исправлен импорт
         let body: Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(body["error"], "Start position  is invalid");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_map_distances_success() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "  # \n####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}/distances?from=0,1", map_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body["data"]["distances"],
+            json!([[1, 0, null, 2], [null, null, null, null]])
+        );
+        assert_eq!(body["data"]["heatmap"], "10#2\n####");
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/distances?from=0,1&max_expanded=1",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "budget_exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_map_distances_invalid_source() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "  # \n####").await;
+
+        for from in ["0,2", "5,5", "garbage"] {
+            let request = Request::builder()
+                .method("GET")
+                .uri(format!("/v1/maps/{}/distances?from={}", map_id, from))
+                .body(Body::empty())
+                .unwrap();
+
+            let response = app.clone().oneshot(request).await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+
+            assert_eq!(body["error"], "Source position is invalid");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_map_components_reports_unreachable_pockets() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#i ##\n#####\n## ##\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}/components", map_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["connected"], false);
+        assert_eq!(body["data"]["components"].as_array().unwrap().len(), 2);
+        assert_eq!(body["data"]["components"][1]["size"], 1);
+        assert_eq!(body["data"]["start_component"], 0);
+        assert_eq!(body["data"]["unreachable_components"], json!([1]));
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}/components?max_expanded=1", map_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_create_map_require_connected() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/maps")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "map_string": " # #\n####", "require_connected": true }).to_string(),
+            ))
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["error"], "Map is not fully connected");
+
+        // Without the flag the same map is accepted
+        create_test_map(&app, " # #\n####").await;
+    }
+
+    #[tokio::test]
+    async fn test_map_metrics_uses_declared_endpoints() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#i  #\n## ##\n##O##\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}/metrics", map_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["dead_ends"], 3);
+        assert_eq!(body["data"]["junctions"], 1);
+        assert_eq!(body["data"]["diameter"], 3);
+        assert_eq!(body["data"]["solution_length"], 3);
+        assert_eq!(body["data"]["is_perfect"], true);
+        assert!(body["data"]["difficulty"].is_number());
+    }
+
+    #[tokio::test]
+    async fn test_map_metrics_rejects_lone_endpoint() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let plain = create_test_map(&app, "#####\n#   #\n## ##\n## ##\n#####").await;
+        let declared = create_test_map(&app, "#####\n#i  #\n## ##\n## ##\n#####").await;
+
+        let metrics = |id: &str, query: &str| {
+            Request::builder()
+                .method("GET")
+                .uri(format!("/v1/maps/{}/metrics?{}", id, query))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for query in ["start=1,1", "finish=3,2"] {
+            let response = app.clone().oneshot(metrics(&plain, query)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["error"], "Start and finish must be given together");
+        }
+
+        // The missing one may come from the map
+        let response = app
+            .clone()
+            .oneshot(metrics(&declared, "finish=3,2"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["solution_length"], 3);
+
+        // A map that declares only a start still gets its other metrics
+        let response = app.oneshot(metrics(&declared, "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["solution_length"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_map_metrics_budget() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n# # #\n#   #\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}/metrics?max_expanded=10", map_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}/metrics?max_expanded=1000", map_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["diameter"], 4);
+    }
+
+    #[tokio::test]
+    async fn test_place_endpoints_updates_stored_map() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/diameter", map_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["length"], 2);
+        assert_eq!(body["data"]["start"], json!({ "x": 1, "y": 3 }));
+        assert_eq!(body["data"]["finish"], json!({ "x": 1, "y": 1 }));
+        assert_eq!(body["data"]["map_string"], "#####\n#O i#\n#####");
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}", map_id))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["map_string"], "#####\n#O i#\n#####");
+    }
+
+    #[tokio::test]
+    async fn test_diameter_budget() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "     \n # # \n     ").await;
+
+        for method in ["GET", "POST"] {
+            let request = Request::builder()
+                .method(method)
+                .uri(format!("/v1/maps/{}/diameter?max_expanded=20", map_id))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], "budget_exceeded");
+        }
+
+        // The failed placement left the map untouched
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/maps/{}", map_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["map_string"], "     \n # # \n     ");
+    }
+
+    #[tokio::test]
+    async fn test_solve_map_bidirectional_bfs() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=1&finish_y=3&algorithm=bidirectional_bfs",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["path_found"], true);
+        assert_eq!(body["data"]["solution_map"], "#####\n#i.O#\n#####");
+    }
+
+    #[tokio::test]
+    async fn test_solve_map_jps_8_connected() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#   #\n#   #\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=3&finish_y=3&algorithm=jps&connectivity=8",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["path_found"], true);
+        assert_eq!(
+            body["data"]["solution_map"],
+            "#####\n#i  #\n# . #\n#  O#\n#####"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_map_invalidates_hierarchy() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store.clone());
+        let map_id = create_test_map(&app, &vec![" ".repeat(25); 3].join("\n")).await;
+        let solve_uri = format!(
+            "/v1/maps/{}/solve?start_x=1&start_y=0&finish_x=1&finish_y=12&algorithm=hpa",
+            map_id
+        );
+        let id = Uuid::parse_str(&map_id).unwrap();
+        // The abstraction is only built by the first HPA* query
+        assert!(map_store.read().await[&id].hierarchy.get().is_none());
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(&solve_uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["path_found"], true);
+        assert!(map_store.read().await[&id].hierarchy.get().is_some());
+
+        // Wall off the finish column completely
+        let mut rows = vec![" ".repeat(25); 3];
+        for row in rows.iter_mut() {
+            row.replace_range(11..12, "#");
+            row.replace_range(13..14, "#");
+        }
+        let request = Request::builder()
+            .method("PUT")
+            .uri(format!("/v1/maps/{}", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "map_string": rows.join("\n") }).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(map_store.read().await[&id].version, 1);
+        assert!(map_store.read().await[&id].hierarchy.get().is_none());
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(&solve_uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["path_found"], false);
+    }
+
+    #[tokio::test]
+    async fn test_update_map_not_found() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+
+        let request = Request::builder()
+            .method("PUT")
+            .uri(format!("/v1/maps/{}", Uuid::new_v4()))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "map_string": "# #" }).to_string()))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_planning_session_lifecycle() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#######\n#     #\n#     #\n#######").await;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/sessions", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 5 } }).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let session_id = body["data"]["id"].as_str().unwrap().to_string();
+        assert_eq!(
+            body["data"]["solution_map"],
+            "#######\n#i...O#\n#     #\n#######"
+        );
+
+        // Paint a wall across the straight route
+        let request = Request::builder()
+            .method("PATCH")
+            .uri(format!("/v1/sessions/{}/cells", session_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "cells": [{ "x": 1, "y": 3, "wall": true }] }).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["path_found"], true);
+        assert_eq!(body["data"]["path"].as_array().unwrap().len(), 7);
+
+        // Start and finish cannot be painted over
+        let request = Request::builder()
+            .method("PATCH")
+            .uri(format!("/v1/sessions/{}/cells", session_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "cells": [{ "x": 1, "y": 1, "wall": true }] }).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/v1/sessions/{}", session_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/sessions/{}", session_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_session_limits() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router_with_config(
+            map_store,
+            ApiConfig {
+                sessions: SessionConfig {
+                    ttl: Duration::from_millis(200),
+                    max_sessions: 1,
+                    ..SessionConfig::default()
+                },
+                ..ApiConfig::default()
+            },
+        );
+        let map_id = create_test_map(&app, "#######\n#     #\n#     #\n#######").await;
+        let create = || {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/v1/maps/{}/sessions", map_id))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 5 } })
+                        .to_string(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(create()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let session_id = body["data"]["id"].as_str().unwrap().to_string();
+
+        let response = app.clone().oneshot(create()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "too_many_sessions");
+
+        // An idle session expires and frees its slot
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/sessions/{}", session_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app.oneshot(create()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_session_cell_limit() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router_with_config(
+            map_store,
+            ApiConfig {
+                sessions: SessionConfig {
+                    max_cells: 40,
+                    ..SessionConfig::default()
+                },
+                ..ApiConfig::default()
+            },
+        );
+        // 28 cells fit once, a second copy would bring the total to 56
+        let map_id = create_test_map(&app, "#######\n#     #\n#     #\n#######").await;
+        let create = || {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/v1/maps/{}/sessions", map_id))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 5 } })
+                        .to_string(),
+                ))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(create()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(create()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "too_many_sessions");
+    }
+
+    #[tokio::test]
+    async fn test_solve_map_any_angle_waypoints() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, &vec![" ".repeat(20); 16].join("\n")).await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=4&finish_y=7&any_angle=true",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["path"].as_array().unwrap().len(), 10);
+        assert_eq!(body["data"]["waypoints"], json!([[1.5, 1.5], [4.5, 7.5]]));
+    }
+
+    #[tokio::test]
+    async fn test_plan_agents() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#######\n#     #\n#### ##\n#######").await;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/agents", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "agents": [
+                        { "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 5 } },
+                        { "start": { "x": 1, "y": 5 }, "finish": { "x": 1, "y": 1 } }
+                    ]
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["makespan"], 7);
+        assert_eq!(
+            body["data"]["paths"][1][0],
+            json!({ "x": 1, "y": 5, "t": 0 })
+        );
+        assert_eq!(
+            body["data"]["paths"][0][7],
+            json!({ "x": 1, "y": 5, "t": 7 })
+        );
+        assert_eq!(
+            body["data"]["frames"][0],
+            "#######\n#0   1#\n#### ##\n#######"
+        );
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/agents", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "agents": [
+                        { "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 5 } },
+                        { "start": { "x": 1, "y": 2 }, "finish": { "x": 1, "y": 5 } }
+                    ]
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_session_budget() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, &vec![" ".repeat(40); 40].join("\n")).await;
+        let create = |budget: Value| {
+            let mut body = json!({ "start": { "x": 0, "y": 0 }, "finish": { "x": 20, "y": 20 } });
+            body["max_expanded"] = budget;
+            Request::builder()
+                .method("POST")
+                .uri(format!("/v1/maps/{}/sessions", map_id))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(create(json!(5))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = app.clone().oneshot(create(Value::Null)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let created: Value = serde_json::from_slice(&body).unwrap();
+        let session_id = created["data"]["id"].as_str().unwrap().to_string();
+
+        // A repair that runs out of budget leaves the session as it was
+        let on_path = &created["data"]["path"][10];
+        let request = Request::builder()
+            .method("PATCH")
+            .uri(format!("/v1/sessions/{}/cells", session_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "cells": [{ "x": on_path["x"], "y": on_path["y"], "wall": true }],
+                    "max_expanded": 1
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "budget_exceeded");
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!("/v1/sessions/{}", session_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["path"], created["data"]["path"]);
+        assert_eq!(
+            body["data"]["solution_map"],
+            created["data"]["solution_map"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_agents_limits() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#######\n#  #  #\n#######").await;
+        let plan = |body: Value| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/v1/maps/{}/agents", map_id))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(plan(json!({
+                "agents": [{ "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 5 } }]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "unreachable_goal");
+
+        let response = app
+            .clone()
+            .oneshot(plan(json!({
+                "agents": [
+                    { "start": { "x": 1, "y": 1 }, "finish": { "x": 1, "y": 2 } },
+                    { "start": { "x": 1, "y": 2 }, "finish": { "x": 1, "y": 1 } }
+                ],
+                "max_expanded": 3
+            })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "budget_exceeded");
+
+        let cols = multi_agent::MAX_AGENTS + 1;
+        let wide_id = create_test_map(&app, &format!("{0}\n{0}", " ".repeat(cols))).await;
+        let agents: Vec<Value> = (0..cols)
+            .map(|y| json!({ "start": { "x": 0, "y": y }, "finish": { "x": 1, "y": y } }))
+            .collect();
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/agents", wide_id))
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "agents": agents }).to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "too_many_agents");
+    }
+
+    #[tokio::test]
+    async fn test_pursue_target() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "##########\n          \n##########").await;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/pursuit", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "pursuer": { "x": 1, "y": 0 },
+                    "target": { "x": 1, "y": 1 },
+                    "policy": "flee"
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["caught"], true);
+        assert_eq!(body["data"]["time"], 5);
+        assert_eq!(body["data"]["path"][5], json!({ "x": 1, "y": 5, "t": 5 }));
+        assert_eq!(
+            body["data"]["target_path"][5],
+            json!({ "x": 1, "y": 5, "t": 5 })
+        );
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/pursuit", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "pursuer": { "x": 1, "y": 0 },
+                    "target": { "x": 1, "y": 1 },
+                    "route": [{ "x": 1, "y": 5 }]
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/v1/maps/{}/pursuit", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "pursuer": { "x": 1, "y": 0 },
+                    "target": { "x": 1, "y": 1 },
+                    "policy": "flee",
+                    "max_expanded": 15
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "budget_exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_solve_hex_map() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "!hex\n    \n    ").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=1&finish_y=1&algorithm=a_star",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["path"].as_array().unwrap().len(), 3);
+        assert!(body["data"]["solution_map"]
+            .as_str()
+            .unwrap()
+            .starts_with("!hex\n"));
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=1&finish_y=1&connectivity=8",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_solve_layered_map() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/layered/solve")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "map_string": "#####\n#  H#\n#####\n\n#####\n#  H#\n#####",
+                    "start": { "x": 1, "y": 1, "z": 0 },
+                    "finish": { "x": 1, "y": 1, "z": 1 }
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body["data"]["path_found"], true);
+        assert_eq!(body["data"]["path"][3], json!({ "x": 1, "y": 3, "z": 1 }));
+        assert_eq!(
+            body["data"]["solution_map"],
+            "#####\n#i.H#\n#####\n\n#####\n#O.H#\n#####"
+        );
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/layered/solve")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "map_string": "#####\n#  H#\n#####",
+                    "start": { "x": 1, "y": 1, "z": 0 },
+                    "finish": { "x": 1, "y": 1, "z": 1 }
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_solve_map_with_trace() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=1&finish_y=3&trace=true",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(
+            body["data"]["trace"],
+            json!([
+                [0, 1, 1],
+                [1, 1, 1],
+                [0, 1, 2],
+                [1, 1, 2],
+                [0, 1, 3],
+                [1, 1, 3]
+            ])
+        );
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=1&finish_y=3&trace=true&algorithm=jps",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_solve_map_trace_limit() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        // BFS across an open 250x250 map records about 125000 events
+        let map_id = create_test_map(&app, &vec![" ".repeat(250); 250].join("\n")).await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve?start_x=0&start_y=0&finish_x=125&finish_y=125&trace=true",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "trace_too_long");
+    }
+
+    #[tokio::test]
+    async fn test_stream_solve() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve/stream?start_x=1&start_y=1&finish_x=1&finish_y=3",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        // The stream ends after the result event
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let events: Vec<(&str, Value)> = body
+            .split("\n\n")
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| {
+                let (name, data) = chunk.split_once('\n').unwrap();
+                (
+                    name.strip_prefix("event: ").unwrap(),
+                    serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap(),
+                )
+            })
+            .collect();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, "progress");
+        assert_eq!(
+            events[0].1,
+            json!({ "visited": 3, "frontier": 0, "best_distance": 0 })
+        );
+        assert_eq!(events[1].0, "result");
+        assert_eq!(events[1].1["path_found"], true);
+        assert_eq!(events[1].1["solution_map"], "#####\n#i.O#\n#####");
+
+        let request = Request::builder()
+            .method("GET")
+            .uri(format!(
+                "/v1/maps/{}/solve/stream?start_x=1&start_y=1&finish_x=1&finish_y=3&algorithm=hpa",
+                map_id
+            ))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Polls a job until it leaves the queue
+    async fn wait_for_job(app: &axum::Router, job_id: &str) -> Value {
+        for _ in 0..200 {
+            let request = Request::builder()
+                .method("GET")
+                .uri(format!("/v1/jobs/{}", job_id))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            if body["data"]["status"] != "queued" && body["data"]["status"] != "running" {
+                return body["data"].clone();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        panic!("job {} did not finish", job_id);
+    }
+
+    async fn submit_job(app: &axum::Router, job: Value) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/jobs")
+            .header("content-type", "application/json")
+            .body(Body::from(job.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store.clone());
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        // Solve
+        let (status, body) = submit_job(
+            &app,
+            json!({
+                "kind": "solve",
+                "map_id": map_id,
+                "start": {"x": 1, "y": 1},
+                "finish": {"x": 1, "y": 3}
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["data"]["kind"], "solve");
+        let job = wait_for_job(&app, body["data"]["id"].as_str().unwrap()).await;
+        assert_eq!(job["status"], "done");
+        assert_eq!(job["steps"], 3);
+        assert_eq!(job["result"]["solution_map"], "#####\n#i.O#\n#####");
+
+        // Step budget
+        let (_, body) = submit_job(
+            &app,
+            json!({
+                "kind": "solve",
+                "map_id": map_id,
+                "start": {"x": 1, "y": 1},
+                "finish": {"x": 1, "y": 3},
+                "max_steps": 2
+            }),
+        )
+        .await;
+        let job = wait_for_job(&app, body["data"]["id"].as_str().unwrap()).await;
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["error"], "Expanded node limit reached");
+
+        // Generation stores a new map
+        let (_, body) = submit_job(
+            &app,
+            json!({"kind": "generate", "rows": 6, "cols": 8, "seed": 1}),
+        )
+        .await;
+        let job_id = body["data"]["id"].as_str().unwrap().to_string();
+        let job = wait_for_job(&app, &job_id).await;
+        assert_eq!(job["status"], "done");
+        let generated = Uuid::parse_str(job["result"]["id"].as_str().unwrap()).unwrap();
+        assert!(map_store.read().await.contains_key(&generated));
+
+        // Analysis
+        let (_, body) = submit_job(
+            &app,
+            json!({"kind": "analysis", "map_id": map_id, "analysis": "diameter"}),
+        )
+        .await;
+        let job = wait_for_job(&app, body["data"]["id"].as_str().unwrap()).await;
+        assert_eq!(job["result"]["length"], 2);
+        let (_, body) = submit_job(
+            &app,
+            json!({"kind": "analysis", "map_id": map_id, "analysis": "metrics", "max_steps": 1}),
+        )
+        .await;
+        let job = wait_for_job(&app, body["data"]["id"].as_str().unwrap()).await;
+        assert_eq!(job["status"], "failed");
+        assert_eq!(job["error"], "Expanded node limit reached");
+
+        // Invalid requests are rejected before queueing
+        let (status, _) = submit_job(&app, json!({"kind": "generate", "rows": 5, "cols": 8})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = submit_job(
+            &app,
+            json!({"kind": "analysis", "map_id": Uuid::new_v4(), "analysis": "metrics"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Deleting a finished job keeps its result
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/v1/jobs/{}", job_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let job = wait_for_job(&app, &job_id).await;
+        assert_eq!(job["status"], "done");
+
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/v1/jobs/{}", Uuid::new_v4()))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_job() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store.clone());
+
+        let (_, body) = submit_job(
+            &app,
+            json!({"kind": "generate", "rows": 1000, "cols": 1000, "seed": 1}),
+        )
+        .await;
+        let job_id = body["data"]["id"].as_str().unwrap().to_string();
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/v1/jobs/{}", job_id))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // The record stays, and the stopped work stores nothing
+        let job = wait_for_job(&app, &job_id).await;
+        assert_eq!(job["status"], "cancelled");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let job = wait_for_job(&app, &job_id).await;
+        assert_eq!(job["status"], "cancelled");
+        assert!(map_store.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_solve_budget_exceeded() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        for algorithm in ["bfs", "jps", "hpa"] {
+            let request = Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=1&finish_y=3&algorithm={}&max_expanded=1",
+                    map_id, algorithm
+                ))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                body,
+                json!({ "error": "Expanded node limit reached", "code": "budget_exceeded" })
+            );
+        }
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/layered/solve")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({
+                    "map_string": "#####\n#  H#\n#####\n\n#####\n#  H#\n#####",
+                    "start": {"x": 1, "y": 1, "z": 0},
+                    "finish": {"x": 1, "y": 1, "z": 1},
+                    "max_expanded": 2
+                })
+                .to_string(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_map_size_limits() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router_with_config(
+            map_store,
+            ApiConfig {
+                map_limits: MapLimits {
+                    max_rows: 3,
+                    max_cols: 10,
+                    max_cells: 20,
+                },
+                body_limit: BodyLimit(100),
+                ..ApiConfig::default()
+            },
+        );
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/maps")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "map_string": "###\n# #\n# #\n###" }).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({ "error": "Map has more than 3 rows", "code": "too_many_rows" })
+        );
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/maps")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "map_string": "#".repeat(200) }).to_string(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({ "error": "Request body is larger than 100 bytes", "code": "body_too_large" })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_limits_accept_largest_map() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store.clone());
+
+        // 2000x2000, the size the bidirectional BFS test runs on
+        let row = " ".repeat(2000);
+        let map_string = vec![row.as_str(); 2000].join("\n");
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/maps")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "map_string": map_string }).to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(map_store.read().await.len(), 1);
+    }
+
+    async fn cache_stats(app: &axum::Router) -> Value {
+        let request = Request::builder()
+            .method("GET")
+            .uri("/v1/cache/stats")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body["data"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_solve_cache() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n#####").await;
+        let solve = || {
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=1&finish_y=3",
+                    map_id
+                ))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        for _ in 0..2 {
+            let response = app.clone().oneshot(solve()).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["data"]["solution_map"], "#####\n#i.O#\n#####");
+        }
+        let stats = cache_stats(&app).await;
+        assert_eq!(stats["hits"], 1);
+        assert_eq!(stats["misses"], 1);
+        assert_eq!(stats["entries"], 1);
+        assert_eq!(stats["capacity"], 64 * 1024 * 1024);
+        // Only the path of three cells is kept, not the rendered response
+        assert!(stats["bytes"].as_u64().unwrap() < 256);
+
+        // An edit drops the cached result and the next solve sees the new map
+        let request = Request::builder()
+            .method("PUT")
+            .uri(format!("/v1/maps/{}", map_id))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                json!({ "map_string": "#####\n# # #\n#####" }).to_string(),
+            ))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap();
+        assert_eq!(cache_stats(&app).await["entries"], 0);
+
+        let response = app.clone().oneshot(solve()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["data"]["path_found"], false);
+        assert_eq!(cache_stats(&app).await["misses"], 2);
+
+        let request = Request::builder()
+            .method("DELETE")
+            .uri(format!("/v1/maps/{}", map_id))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap();
+        assert_eq!(cache_stats(&app).await["entries"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_solve_batch() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n# # #\n#####").await;
+
+        let batch = |body: Value| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/v1/maps/{}/solve/batch", map_id))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let request = batch(json!({
+            "pairs": [
+                {"start": {"x": 1, "y": 1}, "finish": {"x": 2, "y": 3}},
+                {"start": {"x": 1, "y": 1}, "finish": {"x": 1, "y": 1}},
+                {"start": {"x": 2, "y": 1}, "finish": {"x": 1, "y": 3}}
+            ],
+            "parallel": true
+        }));
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let routes = body["data"].as_array().unwrap();
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[0]["path_found"], true);
+        assert_eq!(routes[0]["cost"], 3);
+        assert_eq!(routes[0]["path"].as_array().unwrap().len(), 4);
+        assert_eq!(routes[1]["cost"], 0);
+        assert_eq!(routes[2]["cost"], 3);
+
+        let request = batch(json!({
+            "pairs": [
+                {"start": {"x": 1, "y": 1}, "finish": {"x": 2, "y": 3}},
+                {"start": {"x": 0, "y": 0}, "finish": {"x": 1, "y": 1}}
+            ]
+        }));
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "Pair 1 has an invalid position");
+
+        let request = batch(json!({ "pairs": [], "algorithm": "jps" }));
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_distance_matrix() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(&app, "#####\n#   #\n  ## \n# # #\n#####").await;
+
+        let matrix = |format: &str, body: Value| {
+            Request::builder()
+                .method("POST")
+                .uri(format!("/v1/maps/{}/distances/matrix{}", map_id, format))
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let points = json!({
+            "points": [
+                {"name": "home", "x": 1, "y": 1},
+                {"name": "gate", "x": 2, "y": 4},
+                {"name": "vault", "x": 3, "y": 3}
+            ]
+        });
+
+        let response = app
+            .clone()
+            .oneshot(matrix("", points.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["data"],
+            json!({
+                "names": ["home", "gate", "vault"],
+                "distances": [[0, 3, null], [3, 0, null], [null, null, 0]]
+            })
+        );
+
+        let response = app
+            .clone()
+            .oneshot(matrix("?format=csv", points))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/csv");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            ",home,gate,vault\nhome,0,3,\ngate,3,0,\nvault,,,0\n"
+        );
+
+        let duplicate = json!({
+            "points": [
+                {"name": "home", "x": 1, "y": 1},
+                {"name": "home", "x": 1, "y": 2}
+            ]
+        });
+        let response = app.clone().oneshot(matrix("", duplicate)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let wall = json!({ "points": [{"name": "corner", "x": 0, "y": 0}] });
+        let response = app.oneshot(matrix("", wall)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "Point 'corner' is invalid");
+    }
+
+    #[tokio::test]
+    async fn test_admin_bulk() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let solvable = create_test_map(&app, "#####\n#i O#\n#####").await;
+        let undeclared = create_test_map(&app, "#####\n#   #\n#####").await;
+
+        let bulk = |body: Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/v1/admin/bulk")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(bulk(json!({ "operation": "solve", "algorithm": "a_star" })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let outcomes = body["data"].as_array().unwrap();
+        assert_eq!(outcomes.len(), 2);
+        for outcome in outcomes {
+            if outcome["name"] == solvable {
+                assert_eq!(outcome["result"]["length"], 2);
+            } else {
+                assert_eq!(outcome["name"], undeclared);
+                assert_eq!(outcome["error"], "Map has no declared start and end");
+            }
+        }
+
+        let response = app
+            .clone()
+            .oneshot(bulk(json!({ "operation": "metrics" })))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|outcome| outcome["result"]["open_cells"] == 3));
+
+        let response = app
+            .oneshot(bulk(json!({ "operation": "rebuild" })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_solve_with_constraints() {
+        let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
+        let app = create_api_router(map_store);
+        let map_id = create_test_map(
+            &app,
+            "#########\n#       #\n# ##### #\n#       #\n#########",
+        )
+        .await;
+
+        let solve = |constraints: &str| {
+            Request::builder()
+                .method("GET")
+                .uri(format!(
+                    "/v1/maps/{}/solve?start_x=1&start_y=1&finish_x=3&finish_y=7&{}",
+                    map_id, constraints
+                ))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let body = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        };
+
+        let response = app.clone().oneshot(solve("avoid=1,4")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let data = body(response).await["data"].clone();
+        assert_eq!(data["path_found"], true);
+        assert!(!data["path"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"x": 1, "y": 4})));
+        assert!(data.get("violated_constraint").is_none());
+
+        for (constraints, violated) in [
+            ("avoid=1,4;3,4", "forbidden_cells"),
+            ("region=0,0,1,9", "required_region"),
+            ("max_length=7", "max_length"),
+        ] {
+            let response = app.clone().oneshot(solve(constraints)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let data = body(response).await["data"].clone();
+            assert_eq!(data["path_found"], false);
+            assert_eq!(data["violated_constraint"], violated);
+        }
+
+        let response = app
+            .clone()
+            .oneshot(solve("region=3,6,1,2&max_length=8"))
+            .await
+            .unwrap();
+        assert_eq!(
+            body(response).await["data"]["path"]
+                .as_array()
+                .unwrap()
+                .len(),
+            9
+        );
+
+        // Cost-based solvers search the blocked copy as well
+        for algorithm in ["dijkstra", "a_star", "jps", "hpa"] {
+            let response = app
+                .clone()
+                .oneshot(solve(&format!(
+                    "avoid=1,4&max_length=8&algorithm={}",
+                    algorithm
+                )))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{}", algorithm);
+            let data = body(response).await["data"].clone();
+            assert_eq!(data["path_found"], true, "{}", algorithm);
+            assert!(!data["path"]
+                .as_array()
+                .unwrap()
+                .contains(&json!({"x": 1, "y": 4})));
+        }
+
+        // Constraints apply to their own request only
+        let response = app.clone().oneshot(solve("")).await.unwrap();
+        assert_eq!(body(response).await["data"]["path_found"], true);
+
+        for invalid in [
+            "avoid=9,9",
+            "region=1,1,0,2",
+            "region=1,1",
+            "max_length=3&trace=true",
+            "max_length=3&any_angle=true",
+        ] {
+            let response = app.clone().oneshot(solve(invalid)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", invalid);
+        }
+    }
+}
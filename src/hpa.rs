@@ -0,0 +1,416 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::graph::{BudgetExceeded, NoObserver, SearchBudget, SearchObserver};
+use crate::map::{Map, Point, SearchResult};
+
+/// Сторона кластера по умолчанию
+pub const CLUSTER_SIZE: usize = 10;
+
+// Входы длиннее этого порога получают два перехода - по краям,
+// короткие - один посередине
+const LONG_ENTRANCE: usize = 6;
+
+type ClusterId = (usize, usize);
+
+// Нет вершины или родителя в плоских буферах
+const NONE: u32 = u32::MAX;
+
+/// Абстракция карты для иерархического поиска (HPA*): карта разбита на
+/// кластеры, между соседними кластерами выбраны переходы, а внутри кластера
+/// переходы соединены рёбрами с длиной кратчайшего локального пути.
+/// Строится один раз для карты (4-связность) и переиспользуется запросами.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hierarchy {
+    cluster_size: usize,
+    /// Число кластеров в строке - для номера кластера
+    cluster_cols: usize,
+    /// Клетки-переходы - вершины абстрактного графа
+    nodes: Vec<Point>,
+    /// Рёбра абстрактного графа: (вершина, длина в шагах)
+    edges: Vec<Vec<(usize, usize)>>,
+    /// Переходы, лежащие в каждом кластере, по номеру кластера
+    cluster_nodes: Vec<Vec<usize>>,
+}
+
+impl Hierarchy {
+    pub fn build(map: &Map) -> Self {
+        Self::with_cluster_size(map, CLUSTER_SIZE)
+    }
+
+    /// `build` в пределах бюджета. Шаги бюджета - раскрытия поиска, поэтому
+    /// сборку ограничивают только время и отмена.
+    pub fn build_budgeted(map: &Map, budget: &SearchBudget) -> Result<Self, BudgetExceeded> {
+        Self::assemble(map, CLUSTER_SIZE, budget)
+    }
+
+    pub fn with_cluster_size(map: &Map, cluster_size: usize) -> Self {
+        Self::assemble(map, cluster_size, &SearchBudget::default())
+            .expect("build without limits is never interrupted")
+    }
+
+    fn assemble(
+        map: &Map,
+        cluster_size: usize,
+        budget: &SearchBudget,
+    ) -> Result<Self, BudgetExceeded> {
+        let cluster_size = cluster_size.max(1);
+        let mut hierarchy = Hierarchy {
+            cluster_size,
+            cluster_cols: map.cols.div_ceil(cluster_size),
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            cluster_nodes: vec![
+                Vec::new();
+                map.rows.div_ceil(cluster_size) * map.cols.div_ceil(cluster_size)
+            ],
+        };
+        // Номер вершины для каждой клетки карты
+        let mut node_index = vec![NONE; map.rows * map.cols];
+
+        // Переходы через нижнюю и правую границы каждого кластера;
+        // последние кластеры граничат с первыми через шов тора
+        for (pair_a, pair_b) in hierarchy.border_pairs(map) {
+            let a = hierarchy.add_node(map, pair_a, &mut node_index);
+            let b = hierarchy.add_node(map, pair_b, &mut node_index);
+            hierarchy.edges[a].push((b, 1));
+            hierarchy.edges[b].push((a, 1));
+        }
+
+        // Рёбра внутри кластеров
+        for cluster in 0..hierarchy.cluster_nodes.len() {
+            budget.check_deadline()?;
+            let nodes = hierarchy.cluster_nodes[cluster].clone();
+            for &node in &nodes {
+                let reachable = hierarchy.local_bfs(map, hierarchy.nodes[node]);
+                for &other in &nodes {
+                    if other == node {
+                        continue;
+                    }
+                    if let Some(distance) = reachable.distance(hierarchy.nodes[other]) {
+                        hierarchy.edges[node].push((other, distance));
+                    }
+                }
+            }
+        }
+
+        Ok(hierarchy)
+    }
+
+    /// Путь от `start` до `finish`: поиск по абстрактному графу и
+    /// уточнение каждого его ребра локальным поиском внутри кластера.
+    /// Путь не всегда кратчайший, но найдётся, если он вообще существует.
+    pub fn find_path(&self, map: &Map, start: Point, finish: Point) -> SearchResult {
+        self.find_path_observed(map, start, finish, &mut NoObserver)
+    }
+
+    /// `find_path`, сообщающий наблюдателю о вершинах абстрактного графа
+    pub fn find_path_observed(
+        &self,
+        map: &Map,
+        start: Point,
+        finish: Point,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> SearchResult {
+        // Временные вершины старта и финиша
+        let start_node = self.nodes.len();
+        let finish_node = start_node + 1;
+        // Рёбра временных вершин: (откуда, куда, длина)
+        let mut extra_edges = Vec::new();
+
+        let from_start = self.local_bfs(map, start);
+        let from_finish = self.local_bfs(map, finish);
+        for (endpoint, point, reachable) in [
+            (start_node, start, &from_start),
+            (finish_node, finish, &from_finish),
+        ] {
+            for &node in &self.cluster_nodes[self.cluster_index(self.cluster_of(point))] {
+                if let Some(distance) = reachable.distance(self.nodes[node]) {
+                    extra_edges.push((endpoint, node, distance));
+                    extra_edges.push((node, endpoint, distance));
+                }
+            }
+        }
+
+        // Короткий путь внутри одного кластера
+        let direct = from_start.distance(finish);
+        let point = |node: usize| match node {
+            n if n == start_node => start,
+            n if n == finish_node => finish,
+            n => self.nodes[n],
+        };
+
+        let mut distances = vec![None; finish_node + 1];
+        let mut parents = vec![NONE; finish_node + 1];
+        let mut heap = BinaryHeap::from([Reverse((0, start_node))]);
+        let mut expanded = 0;
+        distances[start_node] = Some(0);
+        observer.on_push(start);
+
+        while let Some(Reverse((distance, node))) = heap.pop() {
+            if distances[node].is_some_and(|best| distance > best) {
+                continue;
+            }
+            expanded += 1;
+            observer.on_pop(point(node));
+            if node == finish_node || direct.is_some_and(|direct| direct <= distance) {
+                break;
+            }
+            if !observer.keep_going() {
+                return SearchResult {
+                    path: None,
+                    expanded,
+                };
+            }
+
+            let edges = self.edges.get(node).into_iter().flatten().copied();
+            let extra = extra_edges
+                .iter()
+                .filter(|&&(from, _, _)| from == node)
+                .map(|&(_, to, cost)| (to, cost));
+            for (next, cost) in edges.chain(extra) {
+                let new_distance = distance + cost;
+                if distances[next].is_none_or(|old| new_distance < old) {
+                    distances[next] = Some(new_distance);
+                    parents[next] = node as u32;
+                    heap.push(Reverse((new_distance, next)));
+                    observer.on_push(point(next));
+                }
+            }
+        }
+
+        let abstract_length = distances[finish_node];
+        let use_direct = match (direct, abstract_length) {
+            (Some(direct), Some(length)) => direct <= length,
+            (Some(_), None) => true,
+            _ => false,
+        };
+
+        let path = if use_direct {
+            Some(self.local_path(map, start, finish))
+        } else if abstract_length.is_some() {
+            let mut chain = vec![finish_node];
+            let mut current = finish_node;
+            while parents[current] != NONE {
+                current = parents[current] as usize;
+                chain.push(current);
+            }
+            chain.reverse();
+
+            let mut path = vec![start];
+            for pair in chain.windows(2) {
+                let (from, to) = (point(pair[0]), point(pair[1]));
+                if self.cluster_of(from) == self.cluster_of(to) {
+                    path.extend(self.local_path(map, from, to).into_iter().skip(1));
+                } else {
+                    // Переход через границу кластеров - один шаг
+                    path.push(to);
+                }
+            }
+            Some(path)
+        } else {
+            None
+        };
+
+        SearchResult { path, expanded }
+    }
+
+    fn cluster_of(&self, point: Point) -> ClusterId {
+        (point.x / self.cluster_size, point.y / self.cluster_size)
+    }
+
+    fn cluster_index(&self, (cluster_x, cluster_y): ClusterId) -> usize {
+        cluster_x * self.cluster_cols + cluster_y
+    }
+
+    fn add_node(&mut self, map: &Map, point: Point, node_index: &mut [u32]) -> usize {
+        let cell = point.x * map.cols + point.y;
+        if node_index[cell] != NONE {
+            return node_index[cell] as usize;
+        }
+        let index = self.nodes.len();
+        self.nodes.push(point);
+        self.edges.push(Vec::new());
+        let cluster = self.cluster_index(self.cluster_of(point));
+        self.cluster_nodes[cluster].push(index);
+        node_index[cell] = index as u32;
+        index
+    }
+
+    // Пары соседних клеток по разные стороны границ кластеров,
+    // выбранные как переходы
+    fn border_pairs(&self, map: &Map) -> Vec<(Point, Point)> {
+        let mut pairs = Vec::new();
+        let size = self.cluster_size;
+
+        // Горизонтальные границы: последняя строка кластера и следующая за ней
+        if map.rows > size {
+            for first in (size - 1..map.rows).step_by(size).chain([map.rows - 1]) {
+                let second = (first + 1) % map.rows;
+                for block in (0..map.cols).step_by(size) {
+                    let cells = block..(block + size).min(map.cols);
+                    let open: Vec<bool> = cells
+                        .clone()
+                        .map(|y| {
+                            map.is_open(Point { x: first, y })
+                                && map.is_open(Point { x: second, y })
+                        })
+                        .collect();
+                    for y in entrance_cells(&open) {
+                        pairs.push((
+                            Point {
+                                x: first,
+                                y: block + y,
+                            },
+                            Point {
+                                x: second,
+                                y: block + y,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Вертикальные границы
+        if map.cols > size {
+            for first in (size - 1..map.cols).step_by(size).chain([map.cols - 1]) {
+                let second = (first + 1) % map.cols;
+                for block in (0..map.rows).step_by(size) {
+                    let cells = block..(block + size).min(map.rows);
+                    let open: Vec<bool> = cells
+                        .clone()
+                        .map(|x| {
+                            map.is_open(Point { x, y: first })
+                                && map.is_open(Point { x, y: second })
+                        })
+                        .collect();
+                    for x in entrance_cells(&open) {
+                        pairs.push((
+                            Point {
+                                x: block + x,
+                                y: first,
+                            },
+                            Point {
+                                x: block + x,
+                                y: second,
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        pairs.sort_by_key(|&(a, b)| (a.x, a.y, b.x, b.y));
+        pairs.dedup();
+        pairs
+    }
+
+    // BFS внутри кластера клетки `source`
+    fn local_bfs(&self, map: &Map, source: Point) -> LocalTree {
+        let size = self.cluster_size;
+        let cluster = self.cluster_of(source);
+        let mut tree = LocalTree {
+            corner: Point {
+                x: cluster.0 * size,
+                y: cluster.1 * size,
+            },
+            size,
+            distances: vec![None; size * size],
+            parents: vec![NONE; size * size],
+        };
+        let mut queue = VecDeque::from([source]);
+        let source_index = tree.index(source);
+        tree.distances[source_index] = Some(0);
+
+        while let Some(current) = queue.pop_front() {
+            let current_index = tree.index(current);
+            let distance = tree.distances[current_index].unwrap_or(0);
+            for neighbor in map.get_neighbors(current) {
+                if self.cluster_of(neighbor) != cluster {
+                    continue;
+                }
+                let index = tree.index(neighbor);
+                if tree.distances[index].is_none() {
+                    tree.distances[index] = Some(distance + 1);
+                    tree.parents[index] = current_index as u32;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        tree
+    }
+
+    // Кратчайший путь между клетками одного кластера, не выходящий из него
+    fn local_path(&self, map: &Map, from: Point, to: Point) -> Vec<Point> {
+        let reachable = self.local_bfs(map, from);
+        let mut path = vec![to];
+        let mut index = reachable.index(to);
+        while reachable.parents[index] != NONE {
+            index = reachable.parents[index] as usize;
+            path.push(reachable.point(index));
+        }
+        path.reverse();
+        path
+    }
+}
+
+// Дерево BFS внутри одного кластера; буферы индексируются положением
+// клетки в кластере
+struct LocalTree {
+    corner: Point,
+    size: usize,
+    distances: Vec<Option<usize>>,
+    parents: Vec<u32>,
+}
+
+impl LocalTree {
+    fn index(&self, point: Point) -> usize {
+        (point.x - self.corner.x) * self.size + (point.y - self.corner.y)
+    }
+
+    fn point(&self, index: usize) -> Point {
+        Point {
+            x: self.corner.x + index / self.size,
+            y: self.corner.y + index % self.size,
+        }
+    }
+
+    // Расстояние до клетки или `None`, если её не достичь внутри кластера
+    fn distance(&self, point: Point) -> Option<usize> {
+        let inside = (self.corner.x..self.corner.x + self.size).contains(&point.x)
+            && (self.corner.y..self.corner.y + self.size).contains(&point.y);
+        if inside {
+            self.distances[self.index(point)]
+        } else {
+            None
+        }
+    }
+}
+
+// Переходы на отрезке границы: для каждого максимального участка, свободного
+// с обеих сторон, - середина или оба конца
+fn entrance_cells(open: &[bool]) -> Vec<usize> {
+    let mut cells = Vec::new();
+    let mut index = 0;
+
+    while index < open.len() {
+        if !open[index] {
+            index += 1;
+            continue;
+        }
+        let begin = index;
+        while index < open.len() && open[index] {
+            index += 1;
+        }
+        let end = index - 1;
+        if end - begin + 1 >= LONG_ENTRANCE {
+            cells.extend([begin, end]);
+        } else {
+            cells.push((begin + end) / 2);
+        }
+    }
+
+    cells
+}
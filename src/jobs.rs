@@ -0,0 +1,257 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+use uuid::Uuid;
+
+use crate::graph::{CancelToken, SearchBudget, SearchObserver};
+
+// Limits of the background job queue
+#[derive(Debug, Clone)]
+pub struct JobConfig {
+    // Jobs running at the same time
+    pub workers: usize,
+    // Jobs waiting for a worker; further submissions are rejected
+    pub queue_limit: usize,
+    // How long a finished job can still be polled
+    pub ttl: Duration,
+    // Upper bounds for the budget a job may ask for, also its default
+    pub max_steps: usize,
+    pub time_limit: Duration,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        JobConfig {
+            workers: std::thread::available_parallelism().map_or(2, |n| n.get()),
+            queue_limit: 64,
+            ttl: Duration::from_secs(600),
+            max_steps: 10_000_000,
+            time_limit: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+// Snapshot of a job as returned to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct JobView {
+    pub id: Uuid,
+    pub kind: &'static str,
+    pub status: JobStatus,
+    // Steps done so far: expanded cells for a solve, carved rooms for
+    // a generated maze
+    pub steps: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SubmitError {
+    QueueFull,
+}
+
+// Work of a job; runs on a blocking thread and reports its steps through
+// the context
+pub type Work = Box<dyn FnOnce(&mut JobContext) -> Result<Value, String> + Send>;
+
+// State shared between the queue and the thread running the job
+#[derive(Default)]
+struct Control {
+    cancel: CancelToken,
+    steps: AtomicUsize,
+}
+
+struct JobRecord {
+    kind: &'static str,
+    status: JobStatus,
+    result: Option<Value>,
+    error: Option<String>,
+    max_steps: usize,
+    time_limit: Duration,
+    finished_at: Option<Instant>,
+    control: Arc<Control>,
+}
+
+// Handed to the work of a running job. The budget carries the job's limits
+// and its cancellation; as a search observer the context counts steps for
+// polling clients.
+pub struct JobContext {
+    pub budget: SearchBudget,
+    control: Arc<Control>,
+}
+
+impl<N> SearchObserver<N> for JobContext {
+    fn on_pop(&mut self, _node: N) {
+        self.control.steps.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+type Jobs = Arc<Mutex<HashMap<Uuid, JobRecord>>>;
+
+// Bounded pool of workers fed from a bounded queue. Finished jobs are
+// kept for `ttl` and dropped lazily on the next access to the queue.
+#[derive(Clone)]
+pub struct JobQueue {
+    jobs: Jobs,
+    sender: mpsc::Sender<(Uuid, Work)>,
+    config: JobConfig,
+}
+
+impl JobQueue {
+    // Spawns the workers; must be called inside a tokio runtime
+    pub fn start(config: JobConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.queue_limit.max(1));
+        let receiver = Arc::new(AsyncMutex::new(receiver));
+        let jobs: Jobs = Arc::default();
+
+        for _ in 0..config.workers.max(1) {
+            tokio::spawn(run_worker(receiver.clone(), jobs.clone()));
+        }
+
+        JobQueue {
+            jobs,
+            sender,
+            config,
+        }
+    }
+
+    // Queues the work with the requested budget, capped by the configured
+    // limits
+    pub fn submit(
+        &self,
+        kind: &'static str,
+        max_steps: Option<usize>,
+        time_limit: Option<Duration>,
+        work: Work,
+    ) -> Result<Uuid, SubmitError> {
+        let id = Uuid::new_v4();
+        let mut jobs = self.lock();
+        let permit = self
+            .sender
+            .try_reserve()
+            .map_err(|_| SubmitError::QueueFull)?;
+
+        jobs.insert(
+            id,
+            JobRecord {
+                kind,
+                status: JobStatus::Queued,
+                result: None,
+                error: None,
+                max_steps: max_steps.map_or(self.config.max_steps, |steps| {
+                    steps.min(self.config.max_steps)
+                }),
+                time_limit: time_limit.map_or(self.config.time_limit, |limit| {
+                    limit.min(self.config.time_limit)
+                }),
+                finished_at: None,
+                control: Arc::default(),
+            },
+        );
+        permit.send((id, work));
+        Ok(id)
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<JobView> {
+        let jobs = self.lock();
+        let job = jobs.get(&id)?;
+
+        Some(JobView {
+            id,
+            kind: job.kind,
+            status: job.status,
+            steps: job.control.steps.load(Ordering::Relaxed),
+            result: job.result.clone(),
+            error: job.error.clone(),
+        })
+    }
+
+    // Stops the job if it is still queued or running; the record stays
+    // until `ttl` so clients can see that it was cancelled
+    pub fn cancel(&self, id: Uuid) -> bool {
+        let mut jobs = self.lock();
+        let Some(job) = jobs.get_mut(&id) else {
+            return false;
+        };
+        if matches!(job.status, JobStatus::Queued | JobStatus::Running) {
+            job.control.cancel.cancel();
+            job.status = JobStatus::Cancelled;
+            job.finished_at = Some(Instant::now());
+        }
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, JobRecord>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let ttl = self.config.ttl;
+        jobs.retain(|_, job| job.finished_at.is_none_or(|at| at.elapsed() < ttl));
+        jobs
+    }
+}
+
+async fn run_worker(receiver: Arc<AsyncMutex<mpsc::Receiver<(Uuid, Work)>>>, jobs: Jobs) {
+    loop {
+        let Some((id, work)) = receiver.lock().await.recv().await else {
+            break;
+        };
+
+        // A job cancelled while queued is skipped
+        let mut context = {
+            let mut jobs = jobs.lock().unwrap();
+            let Some(job) = jobs.get_mut(&id) else {
+                continue;
+            };
+            if job.status == JobStatus::Cancelled {
+                continue;
+            }
+            job.status = JobStatus::Running;
+            JobContext {
+                budget: SearchBudget {
+                    max_expanded: Some(job.max_steps),
+                    deadline: Some(Instant::now() + job.time_limit),
+                    cancel: Some(job.control.cancel.clone()),
+                },
+                control: job.control.clone(),
+            }
+        };
+
+        let outcome = tokio::task::spawn_blocking(move || work(&mut context))
+            .await
+            .unwrap_or_else(|_| Err("Job failed unexpectedly".to_string()));
+
+        // The outcome of a job cancelled while running is dropped
+        if let Some(job) = jobs
+            .lock()
+            .unwrap()
+            .get_mut(&id)
+            .filter(|job| job.status == JobStatus::Running)
+        {
+            match outcome {
+                Ok(result) => {
+                    job.status = JobStatus::Done;
+                    job.result = Some(result);
+                }
+                Err(error) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(error);
+                }
+            }
+            job.finished_at = Some(Instant::now());
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::graph::{self, BudgetExceeded, Budgeted, Graph, NoObserver, SearchBudget};
+use crate::map::{Cell, GridKind, Map, Point};
+use crate::map_parser::{MapLimits, ParseMapError, ParsePointError};
+
+// Заголовок с осями, по которым карта замыкается, например `!wrap xyz`
+const WRAP_HEADER: &str = "!wrap";
+
+/// Клетка многоуровневой карты; `z` - номер слоя
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Point3 {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
+}
+
+/// Оси, по которым карта замыкается. По умолчанию каждый слой - тор,
+/// как обычная карта, а верхний и нижний слои не соединены.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wrap {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+}
+
+impl Default for Wrap {
+    fn default() -> Self {
+        Wrap {
+            x: true,
+            y: true,
+            z: false,
+        }
+    }
+}
+
+/// Многоуровневая карта: слои одинакового размера, соединённые лестницами.
+/// Подняться или спуститься можно с лестницы `H` на лестницу, стоящую
+/// в той же клетке соседнего слоя.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayeredMap {
+    pub layers: Vec<Map>,
+    pub rows: usize,
+    pub cols: usize,
+    pub wrap: Wrap,
+}
+
+impl LayeredMap {
+    pub fn depth(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn cell(&self, point: Point3) -> Cell {
+        self.layers[point.z].cell(Point {
+            x: point.x,
+            y: point.y,
+        })
+    }
+
+    pub fn validate_coordinates(&self, point: Point3) -> bool {
+        point.z < self.depth()
+            && self.layers[point.z].validate_coordinates(Point {
+                x: point.x,
+                y: point.y,
+            })
+    }
+
+    /// Проходимые соседи: четыре по слою и лестница на соседнем слое
+    pub fn get_neighbors(&self, point: Point3) -> Vec<Point3> {
+        let mut neighbors = Vec::new();
+        let planar = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+        for (dx, dy) in planar {
+            let x = step(point.x, dx, self.rows, self.wrap.x);
+            let y = step(point.y, dy, self.cols, self.wrap.y);
+            if let (Some(x), Some(y)) = (x, y) {
+                neighbors.push(Point3 { x, y, z: point.z });
+            }
+        }
+
+        if self.cell(point) == Cell::Stairs {
+            for dz in [-1, 1] {
+                if let Some(z) = step(point.z, dz, self.depth(), self.wrap.z) {
+                    let above = Point3 { z, ..point };
+                    if self.cell(above) == Cell::Stairs {
+                        neighbors.push(above);
+                    }
+                }
+            }
+        }
+
+        neighbors.retain(|&neighbor| self.cell(neighbor) != Cell::Wall);
+        neighbors
+    }
+
+    /// Кратчайший по числу ходов путь (BFS); подъём по лестнице - один ход
+    #[allow(dead_code)] // используется в тестах
+    pub fn find_path(&self, start: Point3, finish: Point3) -> Option<Vec<Point3>> {
+        graph::bfs(self, start, Some(finish)).path_to(self, finish)
+    }
+
+    /// `find_path` в пределах бюджета
+    pub fn find_path_budgeted(
+        &self,
+        start: Point3,
+        finish: Point3,
+        budget: &SearchBudget,
+    ) -> Result<Option<Vec<Point3>>, BudgetExceeded> {
+        let mut unobserved = NoObserver;
+        let mut observer = Budgeted::new(budget, &mut unobserved);
+        let tree = graph::bfs_observed(self, start, Some(finish), &mut observer);
+        observer.finish(tree.path_to(self, finish))
+    }
+
+    /// Отмечает путь на слоях; лестницы остаются видны
+    pub fn mark_path(&mut self, path: &[Point3]) {
+        for (index, point) in path.iter().enumerate() {
+            let cell = match index {
+                0 => Cell::Start,
+                i if i == path.len() - 1 => Cell::End,
+                _ if self.cell(*point) == Cell::Stairs => Cell::Stairs,
+                _ => Cell::Path,
+            };
+            let flat = Point {
+                x: point.x,
+                y: point.y,
+            };
+            self.layers[point.z].set_cell(flat, cell);
+        }
+    }
+}
+
+impl Graph for LayeredMap {
+    type Node = Point3;
+
+    fn node_count(&self) -> usize {
+        self.depth() * self.rows * self.cols
+    }
+
+    fn index(&self, point: Point3) -> usize {
+        (point.z * self.rows + point.x) * self.cols + point.y
+    }
+
+    fn node(&self, index: usize) -> Point3 {
+        Point3 {
+            x: index / self.cols % self.rows,
+            y: index % self.cols,
+            z: index / (self.rows * self.cols),
+        }
+    }
+
+    fn neighbors(&self, point: Point3) -> Vec<(Point3, u32)> {
+        self.get_neighbors(point)
+            .into_iter()
+            .map(|neighbor| (neighbor, 1))
+            .collect()
+    }
+
+    // Число ходов по каждой оси не меньше расстояния по ней
+    fn heuristic(&self, from: Point3, to: Point3) -> u32 {
+        let axis = |a: usize, b: usize, size: usize, wrap: bool| {
+            let distance = a.abs_diff(b);
+            if wrap {
+                distance.min(size - distance)
+            } else {
+                distance
+            }
+        };
+        (axis(from.x, to.x, self.rows, self.wrap.x)
+            + axis(from.y, to.y, self.cols, self.wrap.y)
+            + axis(from.z, to.z, self.depth(), self.wrap.z)) as u32
+    }
+}
+
+// Шаг по оси: с переходом через край или без него
+fn step(coord: usize, delta: isize, size: usize, wrap: bool) -> Option<usize> {
+    let next = coord as isize + delta;
+    if wrap {
+        Some(next.rem_euclid(size as isize) as usize)
+    } else if (0..size as isize).contains(&next) {
+        Some(next as usize)
+    } else {
+        None
+    }
+}
+
+impl LayeredMap {
+    /// Разбор с пределами размера: строки и столбцы ограничены в каждом
+    /// слое, а клетки - на всех слоях вместе
+    pub fn parse_with_limits(s: &str, limits: &MapLimits) -> Result<Self, ParseMapError> {
+        let mut text = s;
+        let mut wrap = Wrap::default();
+
+        // Необязательная первая строка `!wrap <оси>` задаёт замыкание,
+        // например `!wrap z` или `!wrap`
+        if let Some(rest) = text.strip_prefix(WRAP_HEADER) {
+            let (axes, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            wrap = Wrap {
+                x: false,
+                y: false,
+                z: false,
+            };
+            for axis in axes.trim().chars() {
+                match axis {
+                    'x' => wrap.x = true,
+                    'y' => wrap.y = true,
+                    'z' => wrap.z = true,
+                    _ => return Err(ParseMapError::Format),
+                }
+            }
+            text = body;
+        }
+
+        // Слои разделяются пустой строкой
+        let mut layers: Vec<Map> = Vec::new();
+        let mut cells_left = limits.max_cells;
+        for layer in text.split("\n\n") {
+            let layer_limits = MapLimits {
+                max_cells: cells_left,
+                ..*limits
+            };
+            let map =
+                Map::parse_with_limits(layer, &layer_limits).map_err(|error| match error {
+                    ParseMapError::TooManyCells(_) => ParseMapError::TooManyCells(limits.max_cells),
+                    error => error,
+                })?;
+            if map.kind == GridKind::Hex
+                || layers
+                    .first()
+                    .is_some_and(|first| (first.rows, first.cols) != (map.rows, map.cols))
+            {
+                return Err(ParseMapError::Format);
+            }
+            cells_left -= map.rows * map.cols;
+            layers.push(map);
+        }
+
+        let (rows, cols) = (layers[0].rows, layers[0].cols);
+        Ok(LayeredMap {
+            layers,
+            rows,
+            cols,
+            wrap,
+        })
+    }
+}
+
+impl FromStr for LayeredMap {
+    type Err = ParsePointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LayeredMap::parse_with_limits(s, &MapLimits::UNLIMITED).map_err(|_| ParsePointError)
+    }
+}
+
+impl fmt::Display for LayeredMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.wrap != Wrap::default() {
+            let axes: String = [(self.wrap.x, 'x'), (self.wrap.y, 'y'), (self.wrap.z, 'z')]
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|&(_, axis)| axis)
+                .collect();
+            writeln!(f, "{} {}", WRAP_HEADER, axes)?;
+        }
+        for (z, layer) in self.layers.iter().enumerate() {
+            if z > 0 {
+                write!(f, "\n\n")?;
+            }
+            write!(f, "{}", layer)?;
+        }
+        Ok(())
+    }
+}
@@ -1,39 +1,134 @@
 mod api;
+mod bulk;
+mod cli;
+mod graph;
+mod hpa;
+mod jobs;
+mod layered;
 mod map;
+mod map_any_angle;
+mod map_astar;
+mod map_batch;
+mod map_components;
+mod map_constraints;
+mod map_diameter;
 mod map_formatter;
+mod map_generator;
+mod map_hex;
+mod map_jps;
 mod map_parser;
+mod map_pursuit;
+mod metrics;
+mod multi_agent;
+mod planner;
+mod sessions;
+mod solve_cache;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod handler_tests;
 
-use api::{create_api_router, MapStore};
+use api::{create_api_router_with_config, ApiConfig, BodyLimit, CacheCapacity, MapStore};
 use axum::http::{
     header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
     HeaderValue, Method,
 };
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::sync::RwLock;
 use tower_http::cors::CorsLayer;
 
+fn main() -> ExitCode {
+    // Any arguments select a command line tool instead of the server
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
+
+    let config = match config_from(|name| std::env::var(name).ok()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::from(2);
+        }
+    };
+    serve(config);
+    ExitCode::SUCCESS
+}
+
+// Server settings from environment variables; unset ones keep their defaults
+fn config_from(var: impl Fn(&str) -> Option<String>) -> Result<ApiConfig, String> {
+    let number = |name: &str| -> Result<Option<usize>, String> {
+        var(name)
+            .map(|value| {
+                value.parse().map_err(|_| {
+                    format!("{} must be a non-negative integer, got '{}'", name, value)
+                })
+            })
+            .transpose()
+    };
+
+    let mut config = ApiConfig::default();
+    if let Some(max_rows) = number("MAP_MAX_ROWS")? {
+        config.map_limits.max_rows = max_rows;
+    }
+    if let Some(max_cols) = number("MAP_MAX_COLS")? {
+        config.map_limits.max_cols = max_cols;
+    }
+    if let Some(max_cells) = number("MAP_MAX_CELLS")? {
+        config.map_limits.max_cells = max_cells;
+    }
+    if let Some(body_limit) = number("BODY_LIMIT_BYTES")? {
+        config.body_limit = BodyLimit(body_limit);
+    }
+    if let Some(capacity) = number("SOLVE_CACHE_BYTES")? {
+        config.solve_cache_capacity = CacheCapacity(capacity);
+    }
+    if let Some(workers) = number("JOB_WORKERS")? {
+        config.jobs.workers = workers;
+    }
+    if let Some(queue_limit) = number("JOB_QUEUE_LIMIT")? {
+        config.jobs.queue_limit = queue_limit;
+    }
+    if let Some(ttl) = number("JOB_TTL_SECS")? {
+        config.jobs.ttl = Duration::from_secs(ttl as u64);
+    }
+    if let Some(max_steps) = number("JOB_MAX_STEPS")? {
+        config.jobs.max_steps = max_steps;
+    }
+    if let Some(time_limit) = number("JOB_TIME_LIMIT_MS")? {
+        config.jobs.time_limit = Duration::from_millis(time_limit as u64);
+    }
+    Ok(config)
+}
+
 #[tokio::main]
-async fn main() {
+async fn serve(config: ApiConfig) {
     // Initialize map storage
     let map_store: MapStore = Arc::new(RwLock::new(HashMap::new()));
 
     // Configure CORS
     let cors = CorsLayer::new()
         .allow_origin("*".parse::<HeaderValue>().unwrap())
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
         .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
 
     // Create API router
-    let app = create_api_router(map_store).layer(cors);
+    let app = create_api_router_with_config(map_store, config).layer(cors);
 
     // Start server
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
@@ -43,5 +138,3 @@ async fn main() {
 
     axum::serve(listener, app).await.unwrap();
 }
-
-
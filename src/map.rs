@@ -1,16 +1,85 @@
-use std::collections::VecDeque;
+use std::collections::BTreeMap;
+use std::ops::Index;
+use std::sync::OnceLock;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::graph::{
+    self, BudgetExceeded, Budgeted, Graph, NoObserver, SearchBudget, SearchObserver,
+};
+use crate::hpa::Hierarchy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: usize,
     pub y: usize,
 }
 
+impl Point {
+    fn from_index(index: usize, cols: usize) -> Self {
+        Point {
+            x: index / cols,
+            y: index % cols,
+        }
+    }
+}
+
+/// Расстояния до клеток карты; `None` - стена или недостижимая клетка.
+/// Хранятся построчно в одном массиве, как их возвращает BFS, а в JSON
+/// выводятся массивом строк. `grid[x]` - строка `x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceGrid {
+    cols: usize,
+    distances: Vec<Option<u32>>,
+}
+
+impl DistanceGrid {
+    /// Расстояние до клетки
+    pub fn get(&self, point: Point) -> Option<usize> {
+        self.distances[point.x * self.cols + point.y].map(|d| d as usize)
+    }
+
+    /// Строки сетки сверху вниз
+    pub fn rows(&self) -> impl Iterator<Item = &[Option<u32>]> {
+        self.distances.chunks(self.cols.max(1))
+    }
+}
+
+impl Index<usize> for DistanceGrid {
+    type Output = [Option<u32>];
+
+    fn index(&self, x: usize) -> &[Option<u32>] {
+        &self.distances[x * self.cols..(x + 1) * self.cols]
+    }
+}
+
+impl Serialize for DistanceGrid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows())
+    }
+}
+
+/// Карта хранит стены плоским битовым массивом (бит на клетку, строка за
+/// строкой), а редкие отметки - старт, финиш, путь, лестницы - отдельно.
+/// Клетки читаются и меняются через `cell` / `set_cell`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Map {
-    pub grid: Vec<Vec<Cell>>,
+    walls: Vec<u64>,
+    /// Отметки на свободных клетках по номеру клетки; пустые не хранятся
+    markers: BTreeMap<usize, Cell>,
     pub rows: usize,
     pub cols: usize,
+    pub kind: GridKind,
+}
+
+/// Форма клеток карты
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridKind {
+    #[default]
+    Square,
+    /// Шестиугольники в раскладке odd-r (нечётные строки сдвинуты на
+    /// полклетки вправо), шесть соседей у каждой клетки
+    Hex,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,23 +89,191 @@ pub enum Cell {
     Start,
     End,
     Path,
+    /// Лестница между слоями многоуровневой карты; на обычной карте -
+    /// свободная клетка
+    Stairs,
+}
+
+/// Алгоритм поиска пути
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    #[default]
+    Bfs,
+    /// Встречный BFS от старта и финиша: на больших открытых картах
+    /// обходит заметно меньше клеток
+    BidirectionalBfs,
+    /// Алгоритм Дейкстры: путь минимальной цены без эвристики
+    Dijkstra,
+    /// A* с эвристикой расстояния на торе; учитывает цену диагоналей
+    AStar,
+    /// Jump Point Search: A* по точкам прыжка на равномерной сетке
+    Jps,
+    /// Иерархический поиск (HPA*) по кластерам карты; только 4-связность.
+    /// Путь близок к кратчайшему, но не обязательно кратчайший.
+    Hpa,
+}
+
+/// Связность сетки: 4 соседа или 8 (с диагоналями)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum Connectivity {
+    #[default]
+    #[serde(rename = "4")]
+    Four,
+    /// По диагонали можно пройти, только если обе соседние по стороне
+    /// клетки свободны - срезать угол стены нельзя
+    #[serde(rename = "8")]
+    Eight,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub algorithm: Algorithm,
+    pub connectivity: Connectivity,
+}
+
+impl From<Algorithm> for SearchOptions {
+    fn from(algorithm: Algorithm) -> Self {
+        SearchOptions {
+            algorithm,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub path: Option<Vec<Point>>,
+    /// Сколько клеток было извлечено из очереди
+    pub expanded: usize,
+}
+
+/// Карта как граф для обобщённых алгоритмов поиска (см. `graph`):
+/// клетки нумеруются построчно, ходы - с учётом связности и вида сетки
+pub struct MapGraph<'a> {
+    map: &'a Map,
+    connectivity: Connectivity,
+}
+
+impl Graph for MapGraph<'_> {
+    type Node = Point;
+
+    fn node_count(&self) -> usize {
+        self.map.rows * self.map.cols
+    }
+
+    fn index(&self, point: Point) -> usize {
+        point.x * self.map.cols + point.y
+    }
+
+    fn node(&self, index: usize) -> Point {
+        Point::from_index(index, self.map.cols)
+    }
+
+    fn neighbors(&self, point: Point) -> Vec<(Point, u32)> {
+        self.map.weighted_neighbors(point, self.connectivity)
+    }
+
+    fn heuristic(&self, from: Point, to: Point) -> u32 {
+        self.map.heuristic(from, to, self.connectivity)
+    }
 }
 
 impl Map {
     pub fn new() -> Self {
+        Self::with_size(0, 0)
+    }
+
+    /// Карта заданного размера из пустых клеток
+    pub fn with_size(rows: usize, cols: usize) -> Self {
         Self {
-            grid: Vec::new(),
-            rows: 0,
-            cols: 0,
+            walls: vec![0; (rows * cols).div_ceil(64)],
+            markers: BTreeMap::new(),
+            rows,
+            cols,
+            kind: GridKind::Square,
         }
     }
 
+    /// Карта из строк клеток одинаковой длины
+    pub fn from_grid(grid: &[Vec<Cell>]) -> Self {
+        let mut map = Self::with_size(grid.len(), grid.first().map_or(0, Vec::len));
+        for (x, row) in grid.iter().enumerate() {
+            for (y, &cell) in row.iter().enumerate() {
+                map.set_cell(Point { x, y }, cell);
+            }
+        }
+        map
+    }
+
+    pub fn cell(&self, point: Point) -> Cell {
+        let index = point.x * self.cols + point.y;
+        if self.walls[index / 64] & (1 << (index % 64)) != 0 {
+            Cell::Wall
+        } else {
+            self.markers.get(&index).copied().unwrap_or(Cell::Empty)
+        }
+    }
+
+    pub fn set_cell(&mut self, point: Point, cell: Cell) {
+        let index = point.x * self.cols + point.y;
+        let bit = 1 << (index % 64);
+        match cell {
+            Cell::Wall => self.walls[index / 64] |= bit,
+            _ => self.walls[index / 64] &= !bit,
+        }
+        match cell {
+            Cell::Wall | Cell::Empty => self.markers.remove(&index),
+            _ => self.markers.insert(index, cell),
+        };
+    }
+
+    /// Убирает все отметки указанных видов за один проход
+    pub fn clear_markers(&mut self, kinds: &[Cell]) {
+        self.markers.retain(|_, marker| !kinds.contains(marker));
+    }
+
+    pub fn is_open(&self, point: Point) -> bool {
+        let index = point.x * self.cols + point.y;
+        self.walls[index / 64] & (1 << (index % 64)) == 0
+    }
+
+    /// Клетки строки `x` слева направо
+    pub fn row_cells(&self, x: usize) -> impl Iterator<Item = Cell> + '_ {
+        (0..self.cols).map(move |y| self.cell(Point { x, y }))
+    }
+
     pub fn validate_coordinates(&self, point: Point) -> bool {
-        point.x < self.rows && point.y < self.cols && self.grid[point.x][point.y] != Cell::Wall
+        point.x < self.rows && point.y < self.cols && self.is_open(point)
     }
 
+    /// Первая клетка заданного типа (например, объявленный старт `i`)
+    pub fn find_cell(&self, cell: Cell) -> Option<Point> {
+        let index = match cell {
+            // Отметки хранятся по возрастанию номера клетки
+            Cell::Start | Cell::End | Cell::Path | Cell::Stairs => self
+                .markers
+                .iter()
+                .find(|&(_, &marker)| marker == cell)
+                .map(|(&index, _)| index),
+            Cell::Wall | Cell::Empty => (0..self.rows * self.cols)
+                .find(|&index| self.cell(Point::from_index(index, self.cols)) == cell),
+        }?;
+        Some(Point::from_index(index, self.cols))
+    }
+
+    #[allow(dead_code)] // используется в тестах
     pub fn find_and_mark_path(&mut self, start: Point, finish: Point) -> bool {
-        let path = self.find_path(start, finish);
+        self.find_and_mark_path_with(start, finish, SearchOptions::default())
+    }
+
+    pub fn find_and_mark_path_with(
+        &mut self,
+        start: Point,
+        finish: Point,
+        options: impl Into<SearchOptions>,
+    ) -> bool {
+        let path = self.search(start, finish, options).path;
         match path {
             Some(path) => {
                 self.mark_path(&path);
@@ -46,56 +283,211 @@ impl Map {
         }
     }
 
-    fn find_path(&self, start: Point, finish: Point) -> Option<Vec<Point>> {
-        let mut visited = vec![vec![false; self.cols]; self.rows];
-        let mut parent = vec![vec![None; self.cols]; self.rows];
-        let mut queue = VecDeque::new();
+    pub fn search(
+        &self,
+        start: Point,
+        finish: Point,
+        options: impl Into<SearchOptions>,
+    ) -> SearchResult {
+        self.search_observed(start, finish, options, &mut NoObserver)
+    }
+
+    /// `search_observed` в пределах бюджета; прерванный поиск возвращает
+    /// предел, на котором остановился, а не пустой путь
+    pub fn search_budgeted(
+        &self,
+        start: Point,
+        finish: Point,
+        options: impl Into<SearchOptions>,
+        budget: &SearchBudget,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Result<SearchResult, BudgetExceeded> {
+        self.search_cached(start, finish, options, &OnceLock::new(), budget, observer)
+    }
+
+    /// `search_budgeted` с абстракцией HPA*, общей для поисков по этой
+    /// карте (см. `hierarchy`)
+    pub fn search_cached(
+        &self,
+        start: Point,
+        finish: Point,
+        options: impl Into<SearchOptions>,
+        cached: &OnceLock<Hierarchy>,
+        budget: &SearchBudget,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Result<SearchResult, BudgetExceeded> {
+        let options = options.into();
+        let hierarchy = self.hierarchy(options.algorithm, cached, budget)?;
+        let mut observer = Budgeted::new(budget, observer);
+        let result = self.search_with_hierarchy(start, finish, options, hierarchy, &mut observer);
+        observer.finish(result)
+    }
+
+    /// Абстракция HPA*, если `algorithm` по ней ищет: готовая из `cached`
+    /// или построенная в пределах бюджета и сохранённая туда для следующих
+    /// поисков. Абстракция верна, пока не менялись стены карты.
+    pub fn hierarchy<'a>(
+        &self,
+        algorithm: Algorithm,
+        cached: &'a OnceLock<Hierarchy>,
+        budget: &SearchBudget,
+    ) -> Result<Option<&'a Hierarchy>, BudgetExceeded> {
+        // На шестиугольной сетке HPA* заменяет A*
+        if algorithm != Algorithm::Hpa || self.kind == GridKind::Hex {
+            return Ok(None);
+        }
+        if let Some(hierarchy) = cached.get() {
+            return Ok(Some(hierarchy));
+        }
+        let built = Hierarchy::build_budgeted(self, budget)?;
+        Ok(Some(cached.get_or_init(|| built)))
+    }
 
-        queue.push_back(start);
-        visited[start.x][start.y] = true;
+    /// `search`, сообщающий наблюдателю о каждой клетке, добавленной во
+    /// фронт и раскрытой. JPS и HPA* ищут по своим графам и сообщают
+    /// о точках прыжка и переходах между кластерами, а не о клетках.
+    pub fn search_observed(
+        &self,
+        start: Point,
+        finish: Point,
+        options: impl Into<SearchOptions>,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> SearchResult {
+        self.search_with_hierarchy(start, finish, options, None, observer)
+    }
 
-        while let Some(current) = queue.pop_front() {
-            if current == finish {
-                // Восстанавливаем путь
-                let mut path = Vec::new();
-                let mut point = current;
+    /// `search_observed` с готовой абстракцией HPA*; без неё HPA* строит
+    /// абстракцию заново на каждый поиск
+    pub fn search_with_hierarchy(
+        &self,
+        start: Point,
+        finish: Point,
+        options: impl Into<SearchOptions>,
+        hierarchy: Option<&Hierarchy>,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> SearchResult {
+        let SearchOptions {
+            algorithm,
+            connectivity,
+        } = options.into();
 
-                while point != start {
-                    path.push(point);
-                    if let Some(parent_point) = parent[point.x][point.y] {
-                        point = parent_point;
-                    } else {
-                        break;
+        let graph = self.graph(connectivity);
+        let (path, expanded) = match algorithm {
+            // Цена диагоналей не учитывается: ищется путь с наименьшим
+            // числом ходов
+            Algorithm::Bfs => {
+                let tree = graph::bfs_observed(&graph, start, Some(finish), observer);
+                (tree.path_to(&graph, finish), tree.expanded)
+            }
+            Algorithm::BidirectionalBfs => {
+                graph::bidirectional_bfs_observed(&graph, start, finish, observer)
+            }
+            Algorithm::Dijkstra => {
+                let tree = graph::dijkstra_observed(&graph, start, Some(finish), observer);
+                (tree.path_to(&graph, finish), tree.expanded)
+            }
+            Algorithm::AStar => graph::astar_observed(&graph, start, finish, observer),
+            // Правила отсечения JPS и переходы между кластерами HPA* заданы
+            // для квадратной сетки; на шестиугольной их заменяет A*
+            Algorithm::Jps | Algorithm::Hpa if self.kind == GridKind::Hex => {
+                graph::astar_observed(&graph, start, finish, observer)
+            }
+            Algorithm::Jps => return self.jump_point_search(start, finish, connectivity, observer),
+            Algorithm::Hpa => {
+                return match hierarchy {
+                    Some(hierarchy) => hierarchy.find_path_observed(self, start, finish, observer),
+                    None => {
+                        Hierarchy::build(self).find_path_observed(self, start, finish, observer)
                     }
                 }
-                path.push(start); // Добавляем стартовую точку
-                path.reverse();
-                return Some(path);
             }
+        };
 
-            for neighbor in self.get_neighbors(current) {
-                if !visited[neighbor.x][neighbor.y] {
-                    visited[neighbor.x][neighbor.y] = true;
-                    parent[neighbor.x][neighbor.y] = Some(current);
-                    queue.push_back(neighbor);
-                }
-            }
+        SearchResult { path, expanded }
+    }
+
+    /// Карта как граф для алгоритмов из модуля `graph`
+    pub fn graph(&self, connectivity: Connectivity) -> MapGraph<'_> {
+        MapGraph {
+            map: self,
+            connectivity,
         }
+    }
 
-        None
+    /// BFS-расстояние от `source` до каждой клетки карты.
+    /// Стены и недостижимые клетки остаются `None`.
+    pub fn distances_from(&self, source: Point) -> DistanceGrid {
+        self.distances_observed(source, &mut NoObserver)
     }
 
-    fn mark_path(&mut self, path: &[Point]) {
+    /// `distances_from` с пределами; шагом считается извлечённая из очереди клетка
+    pub fn distances_budgeted(
+        &self,
+        source: Point,
+        budget: &SearchBudget,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Result<DistanceGrid, BudgetExceeded> {
+        let mut observer = Budgeted::new(budget, observer);
+        let distances = self.distances_observed(source, &mut observer);
+        observer.finish(distances)
+    }
+
+    /// `distances_from`, сообщающий наблюдателю о каждом шаге
+    pub fn distances_observed(
+        &self,
+        source: Point,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> DistanceGrid {
+        let graph = self.graph(Connectivity::Four);
+        DistanceGrid {
+            cols: self.cols,
+            distances: graph::bfs_observed(&graph, source, None, observer).distances,
+        }
+    }
+
+    pub fn mark_path(&mut self, path: &[Point]) {
         for (index, point) in path.iter().enumerate() {
-            self.grid[point.x][point.y] = match index {
+            let cell = match index {
                 0 => Cell::Start,
                 i if i == path.len() - 1 => Cell::End,
                 _ => Cell::Path,
             };
+            self.set_cell(*point, cell);
+        }
+    }
+
+    /// Различные открытые соседи клетки: на картах шириной 1-2 клетки сдвиги
+    /// в разные стороны могут вести в одну и ту же клетку или в неё саму
+    pub fn distinct_neighbors(&self, point: Point) -> Vec<Point> {
+        if !self.is_open(point) {
+            return Vec::new();
+        }
+
+        let mut neighbors = self.get_neighbors(point);
+        neighbors.retain(|&neighbor| neighbor != point);
+        neighbors.sort_by_key(|neighbor| (neighbor.x, neighbor.y));
+        neighbors.dedup();
+        neighbors
+    }
+
+    /// Все соседние клетки, включая стены
+    pub fn adjacent(&self, point: Point) -> Vec<Point> {
+        match self.kind {
+            GridKind::Square => [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .map(|&(dx, dy)| self.offset(point, dx, dy))
+                .collect(),
+            GridKind::Hex => self.hex_adjacent(point),
         }
     }
 
     pub fn get_neighbors(&self, point: Point) -> Vec<Point> {
+        if self.kind == GridKind::Hex {
+            let mut neighbors = self.hex_adjacent(point);
+            neighbors.retain(|&neighbor| self.is_open(neighbor));
+            return neighbors;
+        }
+
         let mut neighbors = Vec::new();
         let directions = [(-1, 0), (1, 0), (0, -1), (0, 1)];
 
@@ -117,7 +509,10 @@ impl Map {
                         // Проверяем, что координаты в пределах сетки
                         if new_row_usize < self.rows
                             && new_col_usize < self.cols
-                            && self.grid[new_row_usize][new_col_usize] != Cell::Wall
+                            && self.is_open(Point {
+                                x: new_row_usize,
+                                y: new_col_usize,
+                            })
                         {
                             neighbors.push(Point {
                                 x: new_row_usize,
@@ -0,0 +1,106 @@
+use crate::map::{Map, Point};
+
+// Координаты клетки "развёрнутого" тора: могут выходить за границы карты
+type Unwrapped = (i64, i64);
+
+impl Map {
+    /// Сглаживает путь по клеткам в ломаную ("натягивание нити"): из каждой
+    /// опорной точки идём к самой дальней клетке пути, видимой по прямой.
+    /// Точки - центры клеток в непрерывных координатах развёрнутого тора:
+    /// отрезки могут пересекать швы, поэтому координаты бывают отрицательными
+    /// или больше размеров карты; положение на карте - по модулю её размеров.
+    pub fn smooth_path(&self, path: &[Point]) -> Vec<(f64, f64)> {
+        let unwrapped = self.unwrap_path(path);
+        let mut waypoints = Vec::new();
+
+        if let Some(&first) = unwrapped.first() {
+            waypoints.push(first);
+            let mut anchor = 0;
+            for index in 2..unwrapped.len() {
+                if !self.line_of_sight(unwrapped[anchor], unwrapped[index]) {
+                    anchor = index - 1;
+                    waypoints.push(unwrapped[anchor]);
+                }
+            }
+            if unwrapped.len() > 1 {
+                waypoints.push(unwrapped[unwrapped.len() - 1]);
+            }
+        }
+
+        waypoints
+            .into_iter()
+            .map(|(x, y)| (x as f64 + 0.5, y as f64 + 0.5))
+            .collect()
+    }
+
+    // Переводит путь в координаты развёрнутого тора, чтобы соседние
+    // клетки по разные стороны шва оказались рядом
+    fn unwrap_path(&self, path: &[Point]) -> Vec<Unwrapped> {
+        let step = |from: usize, to: usize, size: usize| -> i64 {
+            match (to + size - from) % size {
+                0 => 0,
+                1 => 1,
+                _ => -1,
+            }
+        };
+
+        let mut unwrapped: Vec<Unwrapped> = Vec::with_capacity(path.len());
+        for (index, point) in path.iter().enumerate() {
+            let next = match index {
+                0 => (point.x as i64, point.y as i64),
+                _ => {
+                    let previous = path[index - 1];
+                    let (x, y) = unwrapped[index - 1];
+                    (
+                        x + step(previous.x, point.x, self.rows),
+                        y + step(previous.y, point.y, self.cols),
+                    )
+                }
+            };
+            unwrapped.push(next);
+        }
+        unwrapped
+    }
+
+    // Прямая видимость между центрами клеток: все клетки, которые задевает
+    // отрезок, свободны. Если отрезок проходит точно через угол клеток,
+    // обе клетки по сторонам угла тоже должны быть свободны.
+    fn line_of_sight(&self, from: Unwrapped, to: Unwrapped) -> bool {
+        let open = |(x, y): Unwrapped| {
+            self.is_open(Point {
+                x: x.rem_euclid(self.rows as i64) as usize,
+                y: y.rem_euclid(self.cols as i64) as usize,
+            })
+        };
+
+        let (nx, ny) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+        let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+        let (mut ix, mut iy) = (0, 0);
+        let mut current = from;
+
+        if !open(current) {
+            return false;
+        }
+        while ix < nx || iy < ny {
+            let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+            if decision == 0 {
+                if !open((current.0 + sx, current.1)) || !open((current.0, current.1 + sy)) {
+                    return false;
+                }
+                current = (current.0 + sx, current.1 + sy);
+                ix += 1;
+                iy += 1;
+            } else if decision < 0 {
+                current.0 += sx;
+                ix += 1;
+            } else {
+                current.1 += sy;
+                iy += 1;
+            }
+            if !open(current) {
+                return false;
+            }
+        }
+        true
+    }
+}
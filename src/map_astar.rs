@@ -0,0 +1,81 @@
+use crate::map::{Connectivity, GridKind, Map, Point};
+
+/// Цена хода по стороне клетки
+pub const STRAIGHT_COST: u32 = 10;
+/// Цена хода по диагонали (≈ 10·√2)
+pub const DIAGONAL_COST: u32 = 14;
+
+const STRAIGHT_DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL_DIRECTIONS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+impl Map {
+    /// Сдвиг клетки с переходом через края карты
+    pub fn offset(&self, point: Point, dx: isize, dy: isize) -> Point {
+        Point {
+            x: (point.x as isize + dx).rem_euclid(self.rows as isize) as usize,
+            y: (point.y as isize + dy).rem_euclid(self.cols as isize) as usize,
+        }
+    }
+
+    /// Можно ли сделать ход в направлении (dx, dy): клетка назначения
+    /// свободна, а для диагонали свободны и обе клетки по сторонам
+    pub fn can_move(&self, point: Point, dx: isize, dy: isize) -> bool {
+        self.is_open(self.offset(point, dx, dy))
+            && (dx == 0
+                || dy == 0
+                || (self.is_open(self.offset(point, dx, 0))
+                    && self.is_open(self.offset(point, 0, dy))))
+    }
+
+    /// Соседи клетки вместе с ценой перехода. На шестиугольной сетке все
+    /// шесть ходов равноценны, связность не учитывается.
+    pub fn weighted_neighbors(
+        &self,
+        point: Point,
+        connectivity: Connectivity,
+    ) -> Vec<(Point, u32)> {
+        if self.kind == GridKind::Hex {
+            return self
+                .get_neighbors(point)
+                .into_iter()
+                .map(|neighbor| (neighbor, STRAIGHT_COST))
+                .collect();
+        }
+
+        let diagonals: &[(isize, isize)] = match connectivity {
+            Connectivity::Four => &[],
+            Connectivity::Eight => &DIAGONAL_DIRECTIONS,
+        };
+
+        STRAIGHT_DIRECTIONS
+            .iter()
+            .map(|&direction| (direction, STRAIGHT_COST))
+            .chain(
+                diagonals
+                    .iter()
+                    .map(|&direction| (direction, DIAGONAL_COST)),
+            )
+            .filter(|&((dx, dy), _)| self.can_move(point, dx, dy))
+            .map(|((dx, dy), cost)| (self.offset(point, dx, dy), cost))
+            .collect()
+    }
+
+    /// Допустимая эвристика: расстояние на торе без учёта стен
+    pub fn heuristic(&self, from: Point, to: Point, connectivity: Connectivity) -> u32 {
+        if self.kind == GridKind::Hex {
+            return STRAIGHT_COST * self.hex_distance(from, to) as u32;
+        }
+
+        let dx = from.x.abs_diff(to.x);
+        let dy = from.y.abs_diff(to.y);
+        let dx = dx.min(self.rows - dx) as u32;
+        let dy = dy.min(self.cols - dy) as u32;
+
+        match connectivity {
+            Connectivity::Four => STRAIGHT_COST * (dx + dy),
+            Connectivity::Eight => {
+                DIAGONAL_COST * dx.min(dy) + STRAIGHT_COST * (dx.max(dy) - dx.min(dy))
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+use std::thread;
+
+use crate::graph::{
+    self, BudgetExceeded, Budgeted, Graph, SearchBudget, SearchObserver, SearchTree,
+};
+use crate::map::{Algorithm, Connectivity, Map, Point};
+
+/// Найденный маршрут: путь и его длина в ходах (BFS) или цена (Дейкстра)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub path: Vec<Point>,
+    pub cost: u32,
+}
+
+/// Останавливает поиск из одной клетки, как только раскрыты все её цели
+struct UntilSettled(HashSet<Point>);
+
+impl SearchObserver<Point> for UntilSettled {
+    fn on_pop(&mut self, point: Point) {
+        self.0.remove(&point);
+    }
+
+    fn keep_going(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+impl Map {
+    /// Маршруты для множества пар (старт, финиш). Для каждого различного
+    /// старта выполняется один поиск (BFS или Дейкстра) до раскрытия всех
+    /// его финишей, а пути к ним восстанавливаются из одного дерева.
+    /// С `parallel` старты делятся между потоками по числу ядер.
+    /// Бюджет ограничивает каждый поиск из старта по отдельности.
+    pub fn solve_batch(
+        &self,
+        pairs: &[(Point, Point)],
+        algorithm: Algorithm,
+        connectivity: Connectivity,
+        budget: &SearchBudget,
+        parallel: bool,
+    ) -> Result<Vec<Option<Route>>, BudgetExceeded> {
+        // Старт -> номера его пар, в порядке первого появления
+        let mut starts: Vec<Point> = Vec::new();
+        let mut by_start: HashMap<Point, Vec<usize>> = HashMap::new();
+        for (index, &(start, _)) in pairs.iter().enumerate() {
+            by_start
+                .entry(start)
+                .or_insert_with(|| {
+                    starts.push(start);
+                    Vec::new()
+                })
+                .push(index);
+        }
+
+        let solve_start = |start: &Point| {
+            let indices = &by_start[start];
+            let targets = indices.iter().map(|&index| pairs[index].1).collect();
+            let routes = self.routes_from(*start, targets, algorithm, connectivity, budget)?;
+            Ok(indices.iter().copied().zip(routes).collect::<Vec<_>>())
+        };
+
+        let solved: Vec<Vec<(usize, Option<Route>)>> = if parallel && starts.len() > 1 {
+            let workers = thread::available_parallelism().map_or(1, |n| n.get());
+            let chunk = starts.len().div_ceil(workers);
+            thread::scope(|scope| {
+                let handles: Vec<_> = starts
+                    .chunks(chunk)
+                    .map(|chunk| {
+                        scope.spawn(|| chunk.iter().map(solve_start).collect::<Result<Vec<_>, _>>())
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("batch worker panicked"))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|chunks| chunks.into_iter().flatten().collect())
+            })?
+        } else {
+            starts.iter().map(solve_start).collect::<Result<_, _>>()?
+        };
+
+        let mut routes = vec![None; pairs.len()];
+        for (index, route) in solved.into_iter().flatten() {
+            routes[index] = route;
+        }
+        Ok(routes)
+    }
+
+    /// Матрица кратчайших расстояний между точками: строка - откуда,
+    /// столбец - куда. Один поиск на точку, до раскрытия всех остальных.
+    /// Недостижимые пары - `None`.
+    pub fn distance_matrix(
+        &self,
+        points: &[Point],
+        algorithm: Algorithm,
+        connectivity: Connectivity,
+        budget: &SearchBudget,
+    ) -> Result<Vec<Vec<Option<u32>>>, BudgetExceeded> {
+        let graph = self.graph(connectivity);
+        points
+            .iter()
+            .map(|&source| {
+                let tree =
+                    self.search_until_settled(source, points, algorithm, connectivity, budget)?;
+                Ok(points
+                    .iter()
+                    .map(|&target| tree.distances[graph.index(target)])
+                    .collect())
+            })
+            .collect()
+    }
+
+    // Маршруты из одного старта ко всем целям, в порядке целей
+    fn routes_from(
+        &self,
+        start: Point,
+        targets: Vec<Point>,
+        algorithm: Algorithm,
+        connectivity: Connectivity,
+        budget: &SearchBudget,
+    ) -> Result<Vec<Option<Route>>, BudgetExceeded> {
+        let graph = self.graph(connectivity);
+        let tree = self.search_until_settled(start, &targets, algorithm, connectivity, budget)?;
+
+        Ok(targets
+            .into_iter()
+            .map(|target| {
+                let path = tree.path_to(&graph, target)?;
+                let cost = tree.distances[graph.index(target)]?;
+                Some(Route { path, cost })
+            })
+            .collect())
+    }
+
+    // Дерево поиска из старта, остановленного после раскрытия всех целей
+    fn search_until_settled(
+        &self,
+        start: Point,
+        targets: &[Point],
+        algorithm: Algorithm,
+        connectivity: Connectivity,
+        budget: &SearchBudget,
+    ) -> Result<SearchTree, BudgetExceeded> {
+        let graph = self.graph(connectivity);
+        let mut settled = UntilSettled(targets.iter().copied().collect());
+        let mut observer = Budgeted::new(budget, &mut settled);
+        let tree = match algorithm {
+            Algorithm::Dijkstra => graph::dijkstra_observed(&graph, start, None, &mut observer),
+            _ => graph::bfs_observed(&graph, start, None, &mut observer),
+        };
+        // Если остановил наблюдатель целей, а не бюджет, - это успех
+        observer.finish(tree)
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::graph::{BudgetExceeded, Budgeted, NoObserver, SearchBudget, SearchObserver};
+use crate::map::{Map, Point};
+
+/// Минимальный прямоугольник, покрывающий компоненту на торе.
+/// Может переходить через край карты: `x + rows` может быть больше `map.rows`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BoundingBox {
+    pub x: usize,
+    pub y: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Component {
+    pub id: usize,
+    pub size: usize,
+    pub bounding_box: BoundingBox,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Components {
+    /// Номер компоненты для каждой клетки; `None` - стена
+    pub labels: Vec<Vec<Option<usize>>>,
+    pub components: Vec<Component>,
+}
+
+impl Components {
+    pub fn component_of(&self, point: Point) -> Option<usize> {
+        self.labels.get(point.x)?.get(point.y).copied().flatten()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.components.len() <= 1
+    }
+}
+
+impl Map {
+    /// Разметка связных областей с учётом тороидальной топологии
+    pub fn connected_components(&self) -> Components {
+        self.connected_components_observed(&mut NoObserver)
+    }
+
+    /// `connected_components` с пределами; шагом считается размеченная клетка
+    pub fn connected_components_budgeted(
+        &self,
+        budget: &SearchBudget,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Result<Components, BudgetExceeded> {
+        let mut observer = Budgeted::new(budget, observer);
+        let components = self.connected_components_observed(&mut observer);
+        observer.finish(components)
+    }
+
+    /// `connected_components`, сообщающий наблюдателю о каждой клетке.
+    /// Если наблюдатель остановил обход, разметка остаётся неполной.
+    pub fn connected_components_observed(
+        &self,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Components {
+        let mut labels = vec![vec![None; self.cols]; self.rows];
+        let mut components = Vec::new();
+        // Общие для всех компонент отметки строк и столбцов; после каждой
+        // компоненты сбрасываются только задетые ею, иначе на карте из
+        // множества мелких компонент обход становится квадратичным
+        let mut used_rows = Touched::new(self.rows);
+        let mut used_cols = Touched::new(self.cols);
+
+        for x in 0..self.rows {
+            for y in 0..self.cols {
+                if !self.is_open(Point { x, y }) || labels[x][y].is_some() {
+                    continue;
+                }
+
+                let id = components.len();
+                let mut size = 0;
+                let mut queue = VecDeque::new();
+
+                labels[x][y] = Some(id);
+                queue.push_back(Point { x, y });
+                observer.on_push(Point { x, y });
+
+                while let Some(current) = queue.pop_front() {
+                    size += 1;
+                    observer.on_pop(current);
+                    if !observer.keep_going() {
+                        return Components { labels, components };
+                    }
+                    used_rows.mark(current.x);
+                    used_cols.mark(current.y);
+
+                    for neighbor in self.get_neighbors(current) {
+                        if labels[neighbor.x][neighbor.y].is_none() {
+                            labels[neighbor.x][neighbor.y] = Some(id);
+                            queue.push_back(neighbor);
+                            observer.on_push(neighbor);
+                        }
+                    }
+                }
+
+                let (x, rows) = used_rows.take_cover();
+                let (y, cols) = used_cols.take_cover();
+
+                components.push(Component {
+                    id,
+                    size,
+                    bounding_box: BoundingBox { x, y, rows, cols },
+                });
+            }
+        }
+
+        Components { labels, components }
+    }
+}
+
+// Отметки индексов одного измерения, задетых компонентой
+struct Touched {
+    used: Vec<bool>,
+    indices: Vec<usize>,
+}
+
+impl Touched {
+    fn new(len: usize) -> Self {
+        Touched {
+            used: vec![false; len],
+            indices: Vec::new(),
+        }
+    }
+
+    fn mark(&mut self, index: usize) {
+        if !self.used[index] {
+            self.used[index] = true;
+            self.indices.push(index);
+        }
+    }
+
+    // Покрытие отмеченных индексов; отметки сбрасываются для следующей
+    // компоненты
+    fn take_cover(&mut self) -> (usize, usize) {
+        self.indices.sort_unstable();
+        let cover = circular_cover(&self.indices, self.used.len());
+        for &index in &self.indices {
+            self.used[index] = false;
+        }
+        self.indices.clear();
+        cover
+    }
+}
+
+// Кратчайший циклический отрезок (начало, длина), покрывающий все отмеченные
+// индексы (`used` упорядочен по возрастанию): он начинается сразу после
+// самого длинного пустого промежутка, из равных - с меньшим началом.
+fn circular_cover(used: &[usize], n: usize) -> (usize, usize) {
+    let mut best_gap = (0, 0); // (начало промежутка, длина)
+
+    for (i, &index) in used.iter().enumerate() {
+        let next = used[(i + 1) % used.len()];
+        let start = (index + 1) % n;
+        let length = (next + n - index - 1) % n;
+        if length > best_gap.1 || (length == best_gap.1 && start < best_gap.0) {
+            best_gap = (start, length);
+        }
+    }
+
+    if best_gap.1 == 0 {
+        (0, n)
+    } else {
+        ((best_gap.0 + best_gap.1) % n, n - best_gap.1)
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+use crate::graph::{
+    self, BudgetExceeded, Budgeted, Graph, NoObserver, SearchBudget, SearchObserver,
+};
+use crate::hpa::Hierarchy;
+use crate::map::{Algorithm, Cell, Map, Point, SearchOptions, SearchResult};
+
+/// Прямоугольная область на торе: `rows` x `cols` клеток от угла (x, y).
+/// Может переходить через край карты.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl Region {
+    /// Клетки области; область должна помещаться в карту
+    pub fn cells<'a>(&self, map: &'a Map) -> impl Iterator<Item = Point> + 'a {
+        let region = *self;
+        (0..region.rows).flat_map(move |dx| {
+            (0..region.cols).map(move |dy| Point {
+                x: (region.x + dx) % map.rows,
+                y: (region.y + dy) % map.cols,
+            })
+        })
+    }
+}
+
+/// Ограничения одного запроса; карта при этом не меняется
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathConstraints {
+    /// Клетки, которые считаются стенами
+    pub forbidden: Vec<Point>,
+    /// Путь должен пройти хотя бы через одну клетку области
+    pub region: Option<Region>,
+    /// Наибольшее допустимое число ходов
+    pub max_length: Option<usize>,
+}
+
+/// Ограничение, из-за которого пути нет
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Constraint {
+    ForbiddenCells,
+    RequiredRegion,
+    MaxLength,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstrainedPath {
+    pub result: SearchResult,
+    /// Задано, если путь есть на карте, но его исключили ограничения.
+    /// Если пути нет и без ограничений, остаётся `None`.
+    pub violated: Option<Constraint>,
+}
+
+// Пути нет из-за ограничения `violated`
+fn infeasible(violated: Constraint, expanded: usize) -> ConstrainedPath {
+    ConstrainedPath {
+        result: SearchResult {
+            path: None,
+            expanded,
+        },
+        violated: Some(violated),
+    }
+}
+
+impl Map {
+    /// Путь с ограничениями. Запрещённые клетки превращаются в стены на
+    /// копии карты, и по ней ищет выбранный алгоритм. `max_length` считает
+    /// ходы, а не цену, поэтому с ним любой алгоритм заменяется BFS, не
+    /// заходящим глубже `max_length`: путь наименьшей цены может оказаться
+    /// длиннее допустимого, когда подходящий путь есть. Без него BFS ищет
+    /// путь с наименьшим числом ходов, остальные - путь наименьшей цены.
+    /// Путь через область собирается из двух деревьев поиска, от старта и
+    /// от финиша (BFS или Дейкстры), через клетку области с наименьшей
+    /// суммой расстояний. Если пути нет, ограничения проверяются по
+    /// очереди: запрещённые клетки, область, длина.
+    pub fn search_constrained(
+        &self,
+        start: Point,
+        finish: Point,
+        options: SearchOptions,
+        constraints: &PathConstraints,
+        cached: &OnceLock<Hierarchy>,
+        budget: &SearchBudget,
+    ) -> Result<ConstrainedPath, BudgetExceeded> {
+        if constraints.forbidden.contains(&start) || constraints.forbidden.contains(&finish) {
+            return Ok(infeasible(Constraint::ForbiddenCells, 0));
+        }
+
+        let mut blocked = self.clone();
+        for &point in &constraints.forbidden {
+            blocked.set_cell(point, Cell::Wall);
+        }
+        // Абстракция HPA* карты годится и для копии, если стены те же;
+        // иначе она строится для копии и только на этот поиск
+        let own = OnceLock::new();
+        let cached = if constraints.forbidden.is_empty() {
+            cached
+        } else {
+            &own
+        };
+        // Абстракция нужна, только если ищет сам выбранный алгоритм
+        let hierarchy = match (constraints.region, constraints.max_length) {
+            (None, None) => blocked.hierarchy(options.algorithm, cached, budget)?,
+            _ => None,
+        };
+
+        let mut unobserved = NoObserver;
+        let mut observer = Budgeted::new(budget, &mut unobserved);
+        let constrained = self.constrained_observed(
+            &blocked,
+            hierarchy,
+            (start, finish),
+            options,
+            constraints,
+            &mut observer,
+        );
+        observer.finish(constrained)
+    }
+
+    // `blocked` - карта с запрещёнными клетками вместо стен
+    fn constrained_observed(
+        &self,
+        blocked: &Map,
+        hierarchy: Option<&Hierarchy>,
+        (start, finish): (Point, Point),
+        options: SearchOptions,
+        constraints: &PathConstraints,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> ConstrainedPath {
+        let graph = blocked.graph(options.connectivity);
+        let counts_moves = constraints.max_length.is_some()
+            || matches!(
+                options.algorithm,
+                Algorithm::Bfs | Algorithm::BidirectionalBfs
+            );
+        // Глубже `max_length` ходов путь всё равно не пройдёт
+        let max_depth = constraints
+            .max_length
+            .filter(|_| counts_moves)
+            .map(|max_length| max_length.min(u32::MAX as usize) as u32);
+        let mut tree = |source, target| {
+            if counts_moves {
+                graph::bfs_bounded_observed(&graph, source, target, max_depth, &mut *observer)
+            } else {
+                graph::dijkstra_observed(&graph, source, target, &mut *observer)
+            }
+        };
+
+        let (path, mut expanded, reachable) = match constraints.region {
+            None if counts_moves => {
+                let from_start = tree(start, Some(finish));
+                (
+                    from_start.path_to(&graph, finish),
+                    from_start.expanded,
+                    false,
+                )
+            }
+            None => {
+                let result = blocked.search_with_hierarchy(
+                    start,
+                    finish,
+                    options,
+                    hierarchy,
+                    &mut *observer,
+                );
+                (result.path, result.expanded, false)
+            }
+            Some(region) => {
+                let from_start = tree(start, None);
+                // Граф неориентированный: путь от финиша, развёрнутый,
+                // ведёт к финишу
+                let from_finish = tree(finish, None);
+                let path = region
+                    .cells(blocked)
+                    .filter_map(|point| {
+                        let index = graph.index(point);
+                        Some((
+                            from_start.distances[index]? + from_finish.distances[index]?,
+                            point,
+                        ))
+                    })
+                    .min_by_key(|&(distance, _)| distance)
+                    .and_then(|(_, meeting)| {
+                        let mut path = from_start.path_to(&graph, meeting)?;
+                        let mut back = from_finish.path_to(&graph, meeting)?;
+                        back.pop();
+                        path.extend(back.into_iter().rev());
+                        Some(path)
+                    });
+                (
+                    path,
+                    from_start.expanded + from_finish.expanded,
+                    from_start.distances[graph.index(finish)].is_some(),
+                )
+            }
+        };
+
+        let violated = match &path {
+            Some(path) => match constraints.max_length {
+                Some(max_length) if path.len() - 1 > max_length => Some(Constraint::MaxLength),
+                _ => None,
+            },
+            // Обрезанный по глубине поиск не отличает длинный путь от
+            // отсутствующего: причину ищем заново без предела длины. Важно
+            // лишь, есть ли путь, поэтому снова ищет BFS
+            None if max_depth.is_some() => {
+                let unbounded = PathConstraints {
+                    max_length: None,
+                    ..constraints.clone()
+                };
+                let retry = self.constrained_observed(
+                    blocked,
+                    None,
+                    (start, finish),
+                    SearchOptions {
+                        algorithm: Algorithm::Bfs,
+                        ..options
+                    },
+                    &unbounded,
+                    observer,
+                );
+                expanded += retry.result.expanded;
+                retry
+                    .violated
+                    .or(retry.result.path.map(|_| Constraint::MaxLength))
+            }
+            None if reachable => Some(Constraint::RequiredRegion),
+            // Пути нет и без области: виноваты запрещённые клетки, если
+            // без них путь находится
+            None if !constraints.forbidden.is_empty() => {
+                let graph = self.graph(options.connectivity);
+                let tree = graph::bfs_observed(&graph, start, Some(finish), observer);
+                expanded += tree.expanded;
+                tree.distances[graph.index(finish)].map(|_| Constraint::ForbiddenCells)
+            }
+            None => None,
+        };
+
+        match violated {
+            Some(violated) => infeasible(violated, expanded),
+            None => ConstrainedPath {
+                result: SearchResult { path, expanded },
+                violated: None,
+            },
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::graph::{
+    self, BudgetExceeded, Budgeted, Graph, NoObserver, SearchBudget, SearchObserver,
+};
+use crate::map::{Cell, Connectivity, Map, Point};
+
+/// Пара открытых клеток с наибольшим кратчайшим расстоянием на торе
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diameter {
+    pub start: Point,
+    pub finish: Point,
+    pub length: usize,
+}
+
+impl Map {
+    /// Диаметр графа открытых клеток. Для лесов (лабиринтов без циклов)
+    /// достаточно двойного обхода каждой компоненты, в общем случае
+    /// запускается BFS из каждой открытой клетки - это квадратичное время,
+    /// поэтому запросы считают диаметр под бюджетом.
+    pub fn diameter(&self) -> Option<Diameter> {
+        self.diameter_observed(&mut NoObserver)
+    }
+
+    /// `diameter` с пределами; шагом считается раскрытая клетка любого
+    /// из обходов
+    pub fn diameter_budgeted(
+        &self,
+        budget: &SearchBudget,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Result<Option<Diameter>, BudgetExceeded> {
+        let mut observer = Budgeted::new(budget, observer);
+        let diameter = self.diameter_observed(&mut observer);
+        observer.finish(diameter)
+    }
+
+    /// `diameter`, сообщающий наблюдателю о каждом шаге обходов. Если
+    /// наблюдатель остановил поиск, результат - лучшее найденное к этому
+    /// моменту.
+    pub fn diameter_observed(&self, observer: &mut impl SearchObserver<Point>) -> Option<Diameter> {
+        let open_points: Vec<Point> = (0..self.rows)
+            .flat_map(|x| (0..self.cols).map(move |y| Point { x, y }))
+            .filter(|&point| self.is_open(point))
+            .collect();
+
+        let components = self.connected_components_observed(observer);
+        if !observer.keep_going() {
+            return None;
+        }
+        let edges = open_points
+            .iter()
+            .map(|&point| self.distinct_neighbors(point).len())
+            .sum::<usize>()
+            / 2;
+        let is_forest = edges + components.components.len() == open_points.len();
+
+        let graph = self.graph(Connectivity::Four);
+        let farthest = |source: Point, observer: &mut _| {
+            let tree = graph::bfs_observed(&graph, source, None, observer);
+            // Самая удалённая достижимая клетка (первая в порядке обхода строк)
+            let mut best = (source, 0);
+            for (index, distance) in tree.distances.iter().enumerate() {
+                if let Some(distance) = *distance {
+                    if distance as usize > best.1 {
+                        best = (graph.node(index), distance as usize);
+                    }
+                }
+            }
+            best
+        };
+
+        let sources: Vec<Point> = if is_forest {
+            // Самая удалённая клетка от произвольной клетки дерева -
+            // один из концов его диаметра
+            let mut representatives = vec![None; components.components.len()];
+            for &point in &open_points {
+                if let Some(id) = components.component_of(point) {
+                    representatives[id].get_or_insert(point);
+                }
+            }
+            let mut ends = Vec::new();
+            for point in representatives.into_iter().flatten() {
+                if !observer.keep_going() {
+                    break;
+                }
+                ends.push(farthest(point, observer).0);
+            }
+            ends
+        } else {
+            open_points
+        };
+
+        let mut best: Option<Diameter> = None;
+        for start in sources {
+            if !observer.keep_going() {
+                break;
+            }
+            let (finish, length) = farthest(start, observer);
+            if best.is_none_or(|best| best.length < length) {
+                best = Some(Diameter {
+                    start,
+                    finish,
+                    length,
+                });
+            }
+        }
+        best
+    }
+
+    /// Ставит метки `i` и `O` на концы диаметра, убирая прежние
+    #[allow(dead_code)] // используется в тестах
+    pub fn place_endpoints(&mut self) -> Option<Diameter> {
+        let diameter = self.diameter()?;
+        self.mark_endpoints(diameter);
+        Some(diameter)
+    }
+
+    /// Ставит метки `i` и `O` на концы уже найденного диаметра
+    pub fn mark_endpoints(&mut self, diameter: Diameter) {
+        self.clear_markers(&[Cell::Start, Cell::End]);
+        self.set_cell(diameter.start, Cell::Start);
+        self.set_cell(diameter.finish, Cell::End);
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt;
 
-use crate::map::{Cell, Map};
+use crate::map::{Cell, DistanceGrid, GridKind, Map};
+use crate::map_hex::HEX_HEADER;
 
 impl Cell {
     pub fn to_char(self) -> char {
@@ -10,21 +11,85 @@ impl Cell {
             Cell::End => 'O',
             Cell::Wall => '#',
             Cell::Path => '.',
+            Cell::Stairs => 'H',
         }
     }
 }
 
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (i, row) in self.grid.iter().enumerate() {
-            for cell in row {
+        if self.kind == GridKind::Hex {
+            writeln!(f, "{}", HEX_HEADER)?;
+        }
+        for i in 0..self.rows {
+            for cell in self.row_cells(i) {
                 write!(f, "{}", cell.to_char())?;
             }
             // Не добавляем новую строку после последней строки
-            if i < self.grid.len() - 1 {
+            if i < self.rows - 1 {
                 writeln!(f)?;
             }
         }
         Ok(())
     }
 }
+
+// Символы тепловой карты: 0-9, a-z, A-Z; всё, что дальше, обозначается '+'
+const HEATMAP_SYMBOLS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+impl Map {
+    /// Текстовая тепловая карта расстояний: стены - `#`,
+    /// недостижимые клетки - пробел, остальные - символ расстояния.
+    pub fn render_heatmap(&self, distances: &DistanceGrid) -> String {
+        let mut lines = Vec::with_capacity(self.rows);
+
+        for (x, distance_row) in distances.rows().enumerate() {
+            let line: String = self
+                .row_cells(x)
+                .zip(distance_row)
+                .map(|(cell, distance)| match (cell, distance) {
+                    (Cell::Wall, _) => '#',
+                    (_, None) => ' ',
+                    (_, Some(d)) => HEATMAP_SYMBOLS
+                        .get(*d as usize)
+                        .map_or('+', |&ch| ch as char),
+                })
+                .collect();
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Матрица расстояний в CSV: первая строка и первый столбец - имена точек,
+/// недостижимые пары - пустые поля. Имена с запятыми, кавычками или
+/// переводами строк берутся в кавычки.
+pub fn distance_matrix_csv(names: &[String], matrix: &[Vec<Option<u32>>]) -> String {
+    let mut csv = String::new();
+    for name in names {
+        csv.push(',');
+        csv.push_str(&csv_field(name));
+    }
+    csv.push('\n');
+
+    for (name, row) in names.iter().zip(matrix) {
+        csv.push_str(&csv_field(name));
+        for distance in row {
+            csv.push(',');
+            if let Some(distance) = distance {
+                csv.push_str(&distance.to_string());
+            }
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
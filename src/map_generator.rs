@@ -0,0 +1,74 @@
+use crate::graph::SearchObserver;
+use crate::map::{Cell, Map, Point};
+
+// Шаги между комнатами: клетка посередине - проход
+const ROOM_STEPS: [(isize, isize); 4] = [(-2, 0), (2, 0), (0, -2), (0, 2)];
+
+/// Генератор псевдослучайных чисел SplitMix64: воспроизводим по зерну
+/// и не требует внешних зависимостей
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+impl Map {
+    /// Совершенный лабиринт на торе (поиск в глубину со случайным порядком
+    /// соседей). Комнаты стоят в клетках с чётными координатами, поэтому
+    /// размеры должны быть чётными - иначе проходы через край не стыкуются.
+    /// Наблюдатель получает каждую вырубленную комнату и может прервать
+    /// генерацию; тогда лабиринт остаётся недорезанным.
+    pub fn generate_maze(
+        rows: usize,
+        cols: usize,
+        seed: u64,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Map {
+        let mut map = Map::with_size(rows, cols);
+        for x in 0..rows {
+            for y in 0..cols {
+                map.set_cell(Point { x, y }, Cell::Wall);
+            }
+        }
+
+        let mut rng = SplitMix(seed);
+        let start = Point { x: 0, y: 0 };
+        let mut stack = vec![start];
+        map.set_cell(start, Cell::Empty);
+        observer.on_pop(start);
+
+        while let Some(&room) = stack.last() {
+            if !observer.keep_going() {
+                break;
+            }
+
+            let unvisited: Vec<(isize, isize)> = ROOM_STEPS
+                .into_iter()
+                .filter(|&(dx, dy)| !map.is_open(map.offset(room, dx, dy)))
+                .collect();
+            if unvisited.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let (dx, dy) = unvisited[rng.below(unvisited.len())];
+            let next = map.offset(room, dx, dy);
+            map.set_cell(map.offset(room, dx / 2, dy / 2), Cell::Empty);
+            map.set_cell(next, Cell::Empty);
+            observer.on_pop(next);
+            stack.push(next);
+        }
+
+        map
+    }
+}
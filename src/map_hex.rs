@@ -0,0 +1,55 @@
+use crate::map::{Map, Point};
+
+/// Первая строка текстового формата шестиугольной карты
+pub const HEX_HEADER: &str = "!hex";
+
+// Раскладка odd-r: нечётные строки сдвинуты на полклетки вправо, поэтому
+// набор соседей сверху и снизу зависит от чётности строки
+const EVEN_ROW_DIRECTIONS: [(isize, isize); 6] =
+    [(0, -1), (0, 1), (-1, -1), (-1, 0), (1, -1), (1, 0)];
+const ODD_ROW_DIRECTIONS: [(isize, isize); 6] = [(0, -1), (0, 1), (-1, 0), (-1, 1), (1, 0), (1, 1)];
+
+impl Map {
+    /// Шесть соседних клеток шестиугольной сетки (включая стены).
+    /// Число строк шестиугольного тора чётно, так что чётность строки
+    /// при переходе через шов сохраняется.
+    pub(crate) fn hex_adjacent(&self, point: Point) -> Vec<Point> {
+        let directions = if point.x.is_multiple_of(2) {
+            &EVEN_ROW_DIRECTIONS
+        } else {
+            &ODD_ROW_DIRECTIONS
+        };
+
+        directions
+            .iter()
+            .map(|&(dx, dy)| self.offset(point, dx, dy))
+            .collect()
+    }
+
+    /// Число шагов между клетками шестиугольного тора без учёта стен
+    pub fn hex_distance(&self, from: Point, to: Point) -> usize {
+        let rows = self.rows as isize;
+        let cols = self.cols as isize;
+        let (q, r) = axial(from.x as isize, from.y as isize);
+
+        // Ближайшая копия цели среди её сдвигов на размеры карты. Строки
+        // дальше половины карты не нужны, а сдвиг по столбцам при большом
+        // числе строк может уйти на несколько периодов из-за наклона осей.
+        let span = rows / cols + 2;
+        (-1..=1)
+            .flat_map(|k| (-span..=span).map(move |m| (k, m)))
+            .map(|(k, m)| {
+                let (tq, tr) = axial(to.x as isize + k * rows, to.y as isize + m * cols);
+                let (dq, dr) = (tq - q, tr - r);
+                ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as usize
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+// Осевые координаты клетки odd-r; деление с округлением вниз работает и
+// для отрицательных строк
+fn axial(row: isize, col: isize) -> (isize, isize) {
+    (col - (row - (row & 1)) / 2, row)
+}
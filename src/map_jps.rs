@@ -0,0 +1,297 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::graph::{Graph, SearchObserver};
+use crate::map::{Connectivity, Map, Point, SearchResult};
+use crate::map_astar::{DIAGONAL_COST, STRAIGHT_COST};
+
+type Direction = (isize, isize);
+
+// Сначала направления по сторонам, затем диагонали
+const ALL_DIRECTIONS: [Direction; 8] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (-1, 1),
+    (1, -1),
+    (1, 1),
+];
+
+// Нет родителя: начало поиска или недостигнутая клетка
+const NO_PARENT: u32 = u32::MAX;
+
+// Откуда пришли в точку прыжка: номер родителя и номер направления в
+// `ALL_DIRECTIONS`. Число шагов не хранится - при разворачивании пути идём
+// назад по направлению, пока не встретим родителя
+#[derive(Clone, Copy)]
+struct Jump {
+    from: u32,
+    direction: u8,
+}
+
+impl Jump {
+    const NONE: Jump = Jump {
+        from: NO_PARENT,
+        direction: 0,
+    };
+
+    fn direction(self) -> Option<Direction> {
+        (self.from != NO_PARENT).then(|| ALL_DIRECTIONS[self.direction as usize])
+    }
+}
+
+impl Map {
+    /// Jump Point Search. Для 8-связной сетки - вариант без срезания углов,
+    /// для 4-связной - аналогичное отсечение симметричных путей, где при
+    /// движении по строкам дополнительно проверяются прыжки вбок.
+    /// Прямые прыжки ограничены длиной ряда, чтобы не ходить по кругу на торе.
+    pub(crate) fn jump_point_search(
+        &self,
+        start: Point,
+        finish: Point,
+        connectivity: Connectivity,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> SearchResult {
+        // Плоские массивы по номерам клеток, как в `graph::SearchTree`
+        let graph = self.graph(connectivity);
+        let mut costs: Vec<Option<u32>> = vec![None; graph.node_count()];
+        let mut jumps = vec![Jump::NONE; graph.node_count()];
+        let mut closed = vec![false; graph.node_count()];
+        let mut heap = BinaryHeap::new();
+        let mut expanded = 0;
+
+        costs[graph.index(start)] = Some(0);
+        heap.push(Reverse((
+            self.heuristic(start, finish, connectivity),
+            0,
+            graph.index(start),
+        )));
+        observer.on_push(start);
+
+        while let Some(Reverse((_, cost, index))) = heap.pop() {
+            if closed[index] {
+                continue;
+            }
+            closed[index] = true;
+            expanded += 1;
+
+            let current = graph.node(index);
+            observer.on_pop(current);
+            if current == finish {
+                return SearchResult {
+                    path: Some(self.unfold_jumps(&graph, &jumps, start, finish)),
+                    expanded,
+                };
+            }
+            if !observer.keep_going() {
+                break;
+            }
+
+            let came_from = jumps[index].direction();
+            for direction in self.pruned_directions(current, came_from, connectivity) {
+                let Some((point, steps)) = self.jump(current, direction, finish, connectivity)
+                else {
+                    continue;
+                };
+
+                let step_cost = if direction.0 != 0 && direction.1 != 0 {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                let new_cost = cost + step_cost * steps as u32;
+                let target = graph.index(point);
+                if costs[target].is_none_or(|old| new_cost < old) {
+                    costs[target] = Some(new_cost);
+                    jumps[target] = Jump {
+                        from: index as u32,
+                        direction: ALL_DIRECTIONS
+                            .iter()
+                            .position(|&known| known == direction)
+                            .expect("направление из ALL_DIRECTIONS")
+                            as u8,
+                    };
+                    let estimate = new_cost + self.heuristic(point, finish, connectivity);
+                    heap.push(Reverse((estimate, new_cost, target)));
+                    observer.on_push(point);
+                }
+            }
+        }
+
+        SearchResult {
+            path: None,
+            expanded,
+        }
+    }
+
+    // Направления, которые стоит проверять из точки, в которую пришли
+    // по направлению `came_from`; остальные дают симметричные пути
+    fn pruned_directions(
+        &self,
+        point: Point,
+        came_from: Option<Direction>,
+        connectivity: Connectivity,
+    ) -> Vec<Direction> {
+        let Some((dx, dy)) = came_from else {
+            return ALL_DIRECTIONS
+                .iter()
+                .take(match connectivity {
+                    Connectivity::Four => 4,
+                    Connectivity::Eight => 8,
+                })
+                .copied()
+                .filter(|&(dx, dy)| self.can_move(point, dx, dy))
+                .collect();
+        };
+
+        let open = |dx, dy| self.is_open(self.offset(point, dx, dy));
+        let mut directions = Vec::new();
+
+        if dx != 0 && dy != 0 {
+            // Только для 8-связности
+            if open(dx, 0) {
+                directions.push((dx, 0));
+            }
+            if open(0, dy) {
+                directions.push((0, dy));
+            }
+            if open(dx, 0) && open(0, dy) {
+                directions.push((dx, dy));
+            }
+            return directions;
+        }
+
+        // Перпендикулярные направления
+        let sides = [(dy, dx), (-dy, -dx)];
+        match connectivity {
+            Connectivity::Four => {
+                directions.push((dx, dy));
+                directions.extend(sides);
+            }
+            Connectivity::Eight => {
+                if open(dx, dy) {
+                    directions.push((dx, dy));
+                    for (sx, sy) in sides {
+                        if open(sx, sy) {
+                            directions.push((dx + sx, dy + sy));
+                        }
+                    }
+                }
+                directions.extend(sides.into_iter().filter(|&(sx, sy)| open(sx, sy)));
+            }
+        }
+        directions
+    }
+
+    // Прыжок из `from` в направлении `direction`: следующая точка прыжка
+    // и число шагов до неё
+    fn jump(
+        &self,
+        from: Point,
+        direction: Direction,
+        finish: Point,
+        connectivity: Connectivity,
+    ) -> Option<(Point, usize)> {
+        let (dx, dy) = direction;
+        if dx == 0 || dy == 0 {
+            return self.jump_straight(from, direction, finish, connectivity);
+        }
+
+        let mut current = from;
+        for steps in 1..self.rows * self.cols {
+            if !self.can_move(current, dx, dy) {
+                return None;
+            }
+            current = self.offset(current, dx, dy);
+            if current == from {
+                return None;
+            }
+            if current == finish
+                || self
+                    .jump_straight(current, (dx, 0), finish, connectivity)
+                    .is_some()
+                || self
+                    .jump_straight(current, (0, dy), finish, connectivity)
+                    .is_some()
+            {
+                return Some((current, steps));
+            }
+        }
+        None
+    }
+
+    fn jump_straight(
+        &self,
+        from: Point,
+        direction: Direction,
+        finish: Point,
+        connectivity: Connectivity,
+    ) -> Option<(Point, usize)> {
+        let (dx, dy) = direction;
+        let length = if dx != 0 { self.rows } else { self.cols };
+        let sides = [(dy, dx), (-dy, -dx)];
+        let open = |point: Point, (sx, sy): Direction| self.is_open(self.offset(point, sx, sy));
+
+        let mut current = from;
+        for steps in 1..length {
+            current = self.offset(current, dx, dy);
+            if !self.is_open(current) {
+                return None;
+            }
+            if current == finish {
+                return Some((current, steps));
+            }
+
+            // Вынужденный сосед: сбоку свободно, а сбоку позади - стена
+            let forced = sides
+                .iter()
+                .any(|&(sx, sy)| open(current, (sx, sy)) && !open(current, (sx - dx, sy - dy)));
+            if forced {
+                return Some((current, steps));
+            }
+
+            // В 4-связной сетке при движении по строкам проверяем прыжки вбок
+            if connectivity == Connectivity::Four
+                && dx != 0
+                && sides.iter().any(|&side| {
+                    self.jump_straight(current, side, finish, connectivity)
+                        .is_some()
+                })
+            {
+                return Some((current, steps));
+            }
+        }
+        None
+    }
+
+    // Разворачивает цепочку прыжков в путь по клеткам
+    fn unfold_jumps(
+        &self,
+        graph: &impl Graph<Node = Point>,
+        jumps: &[Jump],
+        start: Point,
+        finish: Point,
+    ) -> Vec<Point> {
+        let mut path = vec![finish];
+        let mut current = finish;
+
+        while current != start {
+            let jump = jumps[graph.index(current)];
+            let Some((dx, dy)) = jump.direction() else {
+                break;
+            };
+            // Прыжок не проходит через родителя повторно (см. `jump`),
+            // поэтому первая встреча с ним - начало прыжка
+            let from = graph.node(jump.from as usize);
+            while current != from {
+                current = self.offset(current, -dx, -dy);
+                path.push(current);
+            }
+        }
+
+        path.reverse();
+        path
+    }
+}
@@ -1,12 +1,17 @@
+use std::fmt;
 use std::str::FromStr;
 
-use crate::map::{Cell, Map};
+use crate::map::{Cell, GridKind, Map, Point};
+use crate::map_hex::HEX_HEADER;
 
 impl Cell {
     pub fn from_char(ch: char) -> Result<Self, String> {
         match ch {
             ' ' => Ok(Cell::Empty),
             '#' => Ok(Cell::Wall),
+            'i' => Ok(Cell::Start),
+            'O' => Ok(Cell::End),
+            'H' => Ok(Cell::Stairs),
             _ => Err(format!("Illegal symbol: {}", ch)),
         }
     }
@@ -15,36 +20,134 @@ impl Cell {
 #[derive(Debug, PartialEq, Eq)]
 pub struct ParsePointError;
 
-impl FromStr for Map {
-    type Err = ParsePointError;
+/// Пределы размера карты, проверяемые по ходу разбора
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapLimits {
+    pub max_rows: usize,
+    pub max_cols: usize,
+    pub max_cells: usize,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<&str> = s.lines().collect();
+impl MapLimits {
+    pub const UNLIMITED: MapLimits = MapLimits {
+        max_rows: usize::MAX,
+        max_cols: usize::MAX,
+        max_cells: usize::MAX,
+    };
+
+    /// Проверка размеров до выделения памяти под сетку
+    pub fn check(&self, rows: usize, cols: usize) -> Result<(), ParseMapError> {
+        if rows > self.max_rows {
+            return Err(ParseMapError::TooManyRows(self.max_rows));
+        }
+        if cols > self.max_cols {
+            return Err(ParseMapError::TooManyCols(self.max_cols));
+        }
+        if rows
+            .checked_mul(cols)
+            .is_none_or(|cells| cells > self.max_cells)
+        {
+            return Err(ParseMapError::TooManyCells(self.max_cells));
+        }
+        Ok(())
+    }
+}
 
-        if lines.is_empty() {
-            return Err(ParsePointError);
+impl Default for MapLimits {
+    fn default() -> Self {
+        MapLimits {
+            max_rows: 2000,
+            max_cols: 2000,
+            max_cells: 4_000_000,
         }
+    }
+}
 
-        let mut map = Map::new();
+/// Почему карта не разобрана; для превышенных пределов - сам предел
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMapError {
+    Format,
+    TooManyRows(usize),
+    TooManyCols(usize),
+    TooManyCells(usize),
+}
+
+impl fmt::Display for ParseMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseMapError::Format => write!(f, "Invalid map format"),
+            ParseMapError::TooManyRows(limit) => write!(f, "Map has more than {} rows", limit),
+            ParseMapError::TooManyCols(limit) => write!(f, "Map has more than {} columns", limit),
+            ParseMapError::TooManyCells(limit) => write!(f, "Map has more than {} cells", limit),
+        }
+    }
+}
 
-        map.rows = lines.len();
-        map.cols = lines[0].len();
+impl Map {
+    /// Разбор карты с пределами размера. Строки читаются по одной, и
+    /// слишком длинная первая строка или лишняя строка останавливают разбор
+    /// раньше, чем под сетку выделится память.
+    pub fn parse_with_limits(s: &str, limits: &MapLimits) -> Result<Self, ParseMapError> {
+        let mut lines = s.lines().peekable();
+        let mut kind = GridKind::Square;
 
-        for line in lines.iter() {
-            if line.len() != map.cols {
-                return Err(ParsePointError);
-            }
+        // Шестиугольная карта начинается с заголовка; на торе с нечётным
+        // числом строк сдвинутые строки не стыкуются через шов
+        if lines.next_if_eq(&HEX_HEADER).is_some() {
+            kind = GridKind::Hex;
+        }
 
-            let mut row_vec = Vec::with_capacity(map.cols);
+        let cols = lines.peek().ok_or(ParseMapError::Format)?.len();
+        if cols > limits.max_cols {
+            return Err(ParseMapError::TooManyCols(limits.max_cols));
+        }
+        // Считаем строки не дальше первой лишней
+        let rows = lines
+            .clone()
+            .take(limits.max_rows.saturating_add(1))
+            .count();
+        limits.check(rows, cols)?;
+
+        if kind == GridKind::Hex && !rows.is_multiple_of(2) {
+            return Err(ParseMapError::Format);
+        }
 
-            for ch in line.chars() {
-                let cell = Cell::from_char(ch).map_err(|_| ParsePointError)?;
-                row_vec.push(cell);
+        let mut map = Map::with_size(rows, cols);
+        map.kind = kind;
+
+        for (x, line) in lines.enumerate() {
+            if line.len() != map.cols {
+                return Err(ParseMapError::Format);
             }
 
-            map.grid.push(row_vec);
+            for (y, ch) in line.chars().enumerate() {
+                let cell = Cell::from_char(ch).map_err(|_| ParseMapError::Format)?;
+                map.set_cell(Point { x, y }, cell);
+            }
         }
 
         Ok(map)
     }
 }
+
+impl FromStr for Map {
+    type Err = ParsePointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Map::parse_with_limits(s, &MapLimits::UNLIMITED).map_err(|_| ParsePointError)
+    }
+}
+
+impl FromStr for Point {
+    type Err = ParsePointError;
+
+    // Формат: "x,y"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s.split_once(',').ok_or(ParsePointError)?;
+
+        Ok(Point {
+            x: x.trim().parse().map_err(|_| ParsePointError)?,
+            y: y.trim().parse().map_err(|_| ParsePointError)?,
+        })
+    }
+}
@@ -0,0 +1,163 @@
+use std::cmp::Reverse;
+
+use crate::graph::{BudgetExceeded, Budgeted, NoObserver, SearchBudget, SearchObserver};
+use crate::map::{Algorithm, Map, Point};
+
+/// Как движется цель
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetPolicy {
+    /// Заранее известный маршрут: клетки, в которых цель окажется на
+    /// шагах 1, 2, ...; дойдя до конца, цель останавливается
+    Route(Vec<Point>),
+    /// Цель уходит кратчайшим путём в достижимую клетку, самую далёкую
+    /// от начальной позиции преследователя, и остаётся там
+    Flee,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interception {
+    /// Путь преследователя по шагам (с ожиданием на месте встречи)
+    pub path: Vec<Point>,
+    /// Положение цели на тех же шагах
+    pub target_path: Vec<Point>,
+    /// Шаг, на котором преследователь и цель оказались в одной клетке
+    pub time: usize,
+}
+
+/// Итог преследования: траектория цели и перехват на ней, если он есть
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pursuit {
+    pub trajectory: Vec<Point>,
+    pub interception: Option<Interception>,
+}
+
+impl Map {
+    /// Траектория цели по шагам, начиная с её стартовой клетки.
+    /// `None`, если маршрут проходит через стену или делает не соседний ход.
+    #[allow(dead_code)] // используется в тестах
+    pub fn target_trajectory(
+        &self,
+        pursuer: Point,
+        target: Point,
+        policy: &TargetPolicy,
+    ) -> Option<Vec<Point>> {
+        self.target_trajectory_observed(pursuer, target, policy, &mut NoObserver)
+    }
+
+    /// Траектория цели и самый ранний её перехват в пределах бюджета,
+    /// общего для всех обходов. `None`, если маршрут цели неверен.
+    pub fn pursue(
+        &self,
+        pursuer: Point,
+        target: Point,
+        policy: &TargetPolicy,
+        budget: &SearchBudget,
+    ) -> Result<Option<Pursuit>, BudgetExceeded> {
+        let mut inner = NoObserver;
+        let mut observer = Budgeted::new(budget, &mut inner);
+        let pursuit = self
+            .target_trajectory_observed(pursuer, target, policy, &mut observer)
+            .map(|trajectory| Pursuit {
+                interception: self.intercept_observed(pursuer, &trajectory, &mut observer),
+                trajectory,
+            });
+        observer.finish(pursuit)
+    }
+
+    fn target_trajectory_observed(
+        &self,
+        pursuer: Point,
+        target: Point,
+        policy: &TargetPolicy,
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Option<Vec<Point>> {
+        match policy {
+            TargetPolicy::Route(route) => {
+                let mut trajectory = vec![target];
+                for &next in route {
+                    let current = trajectory[trajectory.len() - 1];
+                    let is_step = next == current || self.get_neighbors(current).contains(&next);
+                    if !self.validate_coordinates(next) || !is_step {
+                        return None;
+                    }
+                    trajectory.push(next);
+                }
+                Some(trajectory)
+            }
+            TargetPolicy::Flee => {
+                let from_pursuer = self.distances_observed(pursuer, observer);
+                let from_target = self.distances_observed(target, observer);
+                // Самая далёкая от преследователя клетка; среди равных -
+                // ближайшая к цели. Недостижимая для преследователя область
+                // - лучшее укрытие.
+                let refuge = (0..self.rows)
+                    .flat_map(|x| (0..self.cols).map(move |y| Point { x, y }))
+                    .filter(|&point| from_target.get(point).is_some())
+                    .max_by_key(|&point| {
+                        (
+                            from_pursuer.get(point).unwrap_or(usize::MAX),
+                            Reverse(from_target.get(point)),
+                            Reverse((point.x, point.y)),
+                        )
+                    })
+                    .unwrap_or(target);
+                self.search_observed(target, refuge, Algorithm::Bfs, observer)
+                    .path
+            }
+        }
+    }
+
+    /// Самый ранний перехват цели, движущейся по известной траектории.
+    ///
+    /// Поиск идёт в пространстве (клетка, шаг). Так как преследователь
+    /// может ждать на месте, клетка достижима на шаге `t` тогда и только
+    /// тогда, когда BFS-расстояние до неё не больше `t`, поэтому весь
+    /// развёрнутый во времени граф сводится к одному обходу в ширину и
+    /// проверке траектории цели шаг за шагом. Встреча засчитывается,
+    /// когда оба стоят в одной клетке на одном шаге.
+    #[allow(dead_code)] // используется в тестах
+    pub fn intercept(
+        &self,
+        pursuer: Point,
+        target: Point,
+        policy: &TargetPolicy,
+    ) -> Option<Interception> {
+        self.pursue(pursuer, target, policy, &SearchBudget::default())
+            .expect("search without limits is never interrupted")?
+            .interception
+    }
+
+    fn intercept_observed(
+        &self,
+        pursuer: Point,
+        target_path: &[Point],
+        observer: &mut impl SearchObserver<Point>,
+    ) -> Option<Interception> {
+        let distances = self.distances_observed(pursuer, observer);
+
+        // После конца траектории цель стоит на месте, и ждать её дольше,
+        // чем нужно на дорогу до последней клетки, незачем
+        let last = target_path.len() - 1;
+        let (time, meeting) = target_path
+            .iter()
+            .enumerate()
+            .find(|&(t, &point)| distances.get(point).is_some_and(|d| d <= t))
+            .map(|(t, &point)| (t, point))
+            .or_else(|| {
+                let point = target_path[last];
+                distances.get(point).map(|d| (d, point))
+            })?;
+
+        let mut path = self
+            .search_observed(pursuer, meeting, Algorithm::Bfs, observer)
+            .path?;
+        path.resize(time + 1, meeting);
+        let target_path = (0..=time).map(|t| target_path[t.min(last)]).collect();
+
+        Some(Interception {
+            path,
+            target_path,
+            time,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::graph::{BudgetExceeded, Budgeted, NoObserver, SearchBudget, SearchObserver};
+use crate::map::{Map, Point};
+
+/// Характеристики лабиринта для оценки сложности уровня
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MazeMetrics {
+    pub open_cells: usize,
+    /// Клетки с единственным выходом
+    pub dead_ends: usize,
+    /// Клетки с тремя и более выходами
+    pub junctions: usize,
+    /// Среднее число новых направлений на развилке
+    pub branching_factor: f64,
+    /// Доля клеток, лежащих в коридорах (ровно два выхода)
+    pub river: f64,
+    /// Длина коридора -> количество таких коридоров
+    pub corridor_lengths: BTreeMap<usize, usize>,
+    /// Самый длинный из кратчайших путей (в шагах)
+    pub diameter: usize,
+    pub solution_length: Option<usize>,
+    /// Длина решения, делённая на площадь карты
+    pub solution_ratio: Option<f64>,
+    /// Лабиринт связен и не содержит циклов (остовное дерево)
+    pub is_perfect: bool,
+    /// Сводная оценка сложности от 0 до 100
+    pub difficulty: f64,
+}
+
+/// Считает метрики карты; `endpoints` - старт и финиш для длины решения
+#[allow(dead_code)] // используется в тестах
+pub fn compute(map: &Map, endpoints: Option<(Point, Point)>) -> MazeMetrics {
+    compute_budgeted(map, endpoints, &SearchBudget::default(), &mut NoObserver)
+        .expect("search without limits is never interrupted")
+}
+
+/// `compute` с пределами. Дольше всего считается диаметр, на картах
+/// с циклами - за квадратичное время; бюджет ограничивает его обходы,
+/// разметку компонент и поиск решения.
+pub fn compute_budgeted(
+    map: &Map,
+    endpoints: Option<(Point, Point)>,
+    budget: &SearchBudget,
+    observer: &mut impl SearchObserver<Point>,
+) -> Result<MazeMetrics, BudgetExceeded> {
+    let mut observer = Budgeted::new(budget, observer);
+    let degrees: Vec<Vec<usize>> = (0..map.rows)
+        .map(|x| {
+            (0..map.cols)
+                .map(|y| map.distinct_neighbors(Point { x, y }).len())
+                .collect()
+        })
+        .collect();
+
+    let open_points: Vec<Point> = (0..map.rows)
+        .flat_map(|x| (0..map.cols).map(move |y| Point { x, y }))
+        .filter(|&point| map.is_open(point))
+        .collect();
+
+    let open_cells = open_points.len();
+    let degree = |point: &Point| degrees[point.x][point.y];
+
+    let dead_ends = open_points.iter().filter(|p| degree(p) == 1).count();
+    let junction_degrees: Vec<usize> = open_points.iter().map(degree).filter(|&d| d >= 3).collect();
+    let junctions = junction_degrees.len();
+    let branching_factor = if junctions == 0 {
+        0.0
+    } else {
+        junction_degrees.iter().map(|d| d - 1).sum::<usize>() as f64 / junctions as f64
+    };
+
+    let corridor_lengths = corridor_lengths(map, &degrees);
+    let corridor_cells: usize = corridor_lengths.iter().map(|(len, n)| len * n).sum();
+    let river = ratio(corridor_cells, open_cells);
+
+    // Каждое ребро учтено дважды - по разу с каждого конца
+    let edges = open_points.iter().map(degree).sum::<usize>() / 2;
+    let is_perfect = open_cells > 0
+        && edges + 1 == open_cells
+        && map
+            .connected_components_observed(&mut observer)
+            .is_connected();
+
+    let diameter = map
+        .diameter_observed(&mut observer)
+        .map_or(0, |diameter| diameter.length);
+
+    let solution_length = endpoints
+        .and_then(|(start, finish)| map.distances_observed(start, &mut observer).get(finish));
+    let solution_ratio = solution_length.map(|length| ratio(length, map.rows * map.cols));
+
+    // Длина решения относительно открытой площади (или диаметр, если
+    // старт и финиш не заданы), доля тупиков среди точек ветвления
+    // и ветвистость развилок
+    let path_factor = ratio(solution_length.unwrap_or(diameter), open_cells);
+    let dead_end_factor = ratio(dead_ends, dead_ends + junctions);
+    let branching_term = (branching_factor / 3.0).min(1.0);
+    let difficulty = 100.0 * (0.5 * path_factor + 0.25 * dead_end_factor + 0.25 * branching_term);
+
+    observer.finish(MazeMetrics {
+        open_cells,
+        dead_ends,
+        junctions,
+        branching_factor,
+        river,
+        corridor_lengths,
+        diameter,
+        solution_length,
+        solution_ratio,
+        is_perfect,
+        difficulty: (difficulty * 10.0).round() / 10.0,
+    })
+}
+
+// Коридор - максимальная связная цепочка клеток ровно с двумя выходами
+fn corridor_lengths(map: &Map, degrees: &[Vec<usize>]) -> BTreeMap<usize, usize> {
+    let mut visited = vec![vec![false; map.cols]; map.rows];
+    let mut lengths = BTreeMap::new();
+
+    for x in 0..map.rows {
+        for y in 0..map.cols {
+            if degrees[x][y] != 2 || visited[x][y] {
+                continue;
+            }
+
+            let mut length = 0;
+            let mut stack = vec![Point { x, y }];
+            visited[x][y] = true;
+
+            while let Some(current) = stack.pop() {
+                length += 1;
+                for neighbor in map.distinct_neighbors(current) {
+                    if degrees[neighbor.x][neighbor.y] == 2 && !visited[neighbor.x][neighbor.y] {
+                        visited[neighbor.x][neighbor.y] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+
+            *lengths.entry(length).or_insert(0) += 1;
+        }
+    }
+
+    lengths
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::graph::{BudgetExceeded, Budgeted, NoObserver, SearchBudget, SearchObserver};
+use crate::map::{Cell, Map, Point};
+
+// Символы агентов на кадрах анимации
+const AGENT_SYMBOLS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Наибольшее число агентов: у каждого свой символ на кадрах
+pub const MAX_AGENTS: usize = AGENT_SYMBOLS.len();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Agent {
+    pub start: Point,
+    pub goal: Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanError {
+    /// У двух агентов совпадают старты или цели
+    SharedEndpoint,
+    /// Агентов больше, чем `MAX_AGENTS`
+    TooManyAgents,
+    /// Цель агента с этим номером недостижима даже на пустой карте
+    Unreachable(usize),
+    /// Для агента с этим номером не нашлось пути без конфликтов
+    NoPath(usize),
+    /// Планирование прервано по бюджету
+    BudgetExceeded(BudgetExceeded),
+}
+
+// Занятость клеток уже спланированными агентами
+#[derive(Default)]
+struct Reservations {
+    cells: HashSet<(Point, usize)>,
+    /// Переходы (откуда, куда, момент прибытия)
+    moves: HashSet<(Point, Point, usize)>,
+    /// Агент остаётся на цели навсегда, начиная с этого момента
+    parked: HashMap<Point, usize>,
+    /// Последний момент, когда клетка занята проходящим агентом
+    last_visit: HashMap<Point, usize>,
+}
+
+impl Reservations {
+    fn reserve(&mut self, path: &[Point]) {
+        for (t, &point) in path.iter().enumerate() {
+            self.cells.insert((point, t));
+            let last = self.last_visit.entry(point).or_insert(t);
+            *last = (*last).max(t);
+            if t > 0 {
+                self.moves.insert((path[t - 1], point, t));
+            }
+        }
+        if let Some(&goal) = path.last() {
+            self.parked.insert(goal, path.len() - 1);
+        }
+    }
+
+    fn is_free(&self, from: Point, to: Point, t: usize) -> bool {
+        !self.cells.contains(&(to, t))
+            && self.parked.get(&to).is_none_or(|&since| t < since)
+            // Встречный обмен местами
+            && !self.moves.contains(&(to, from, t))
+    }
+
+    fn horizon(&self) -> usize {
+        self.last_visit.values().copied().max().unwrap_or(0)
+    }
+}
+
+/// Приоритетное планирование: агенты планируются по очереди (в порядке
+/// списка) поиском в пространстве-времени, обходя клетки и переходы,
+/// уже занятые агентами с более высоким приоритетом. В один момент
+/// времени два агента не стоят в одной клетке и не меняются местами.
+/// Дойдя до цели, агент остаётся на ней.
+///
+/// Результат - положение каждого агента в каждый момент времени;
+/// все пути дополнены ожиданием на цели до общей длины.
+///
+/// Недостижимые без учёта других агентов цели отсекаются обычным BFS
+/// до поиска в пространстве-времени. Бюджет общий на всех агентов и
+/// считает раскрытые состояния (клетка, момент).
+pub fn plan(
+    map: &Map,
+    agents: &[Agent],
+    budget: &SearchBudget,
+) -> Result<Vec<Vec<Point>>, PlanError> {
+    if agents.len() > MAX_AGENTS {
+        return Err(PlanError::TooManyAgents);
+    }
+    let starts: HashSet<Point> = agents.iter().map(|agent| agent.start).collect();
+    let goals: HashSet<Point> = agents.iter().map(|agent| agent.goal).collect();
+    if starts.len() != agents.len() || goals.len() != agents.len() {
+        return Err(PlanError::SharedEndpoint);
+    }
+    for (index, agent) in agents.iter().enumerate() {
+        let distances = map.distances_from(agent.start);
+        if distances.get(agent.goal).is_none() {
+            return Err(PlanError::Unreachable(index));
+        }
+    }
+
+    let mut reservations = Reservations::default();
+    let mut paths = Vec::with_capacity(agents.len());
+    let mut inner = NoObserver;
+    let mut observer = Budgeted::new(budget, &mut inner);
+
+    for (index, agent) in agents.iter().enumerate() {
+        let Some(path) = space_time_search(map, *agent, &reservations, &mut observer) else {
+            // Поиск без пути - либо настоящий тупик, либо кончился бюджет
+            return Err(match observer.finish(()) {
+                Ok(()) => PlanError::NoPath(index),
+                Err(exceeded) => PlanError::BudgetExceeded(exceeded),
+            });
+        };
+        reservations.reserve(&path);
+        paths.push(path);
+    }
+
+    let makespan = paths.iter().map(Vec::len).max().unwrap_or(0);
+    for path in paths.iter_mut() {
+        if let Some(&goal) = path.last() {
+            path.resize(makespan, goal);
+        }
+    }
+    Ok(paths)
+}
+
+// BFS по состояниям (клетка, момент времени) с возможностью ждать на месте
+fn space_time_search(
+    map: &Map,
+    agent: Agent,
+    reservations: &Reservations,
+    observer: &mut impl SearchObserver<Point>,
+) -> Option<Vec<Point>> {
+    if reservations.cells.contains(&(agent.start, 0)) {
+        return None;
+    }
+
+    // После того как все агенты выше по приоритету встали на цели,
+    // ждать дольше, чем нужно на обход всей карты, бессмысленно
+    let horizon = reservations.horizon() + map.rows * map.cols;
+    let mut parents: HashMap<(Point, usize), Point> = HashMap::new();
+    let mut queue = VecDeque::from([(agent.start, 0)]);
+    let mut visited = HashSet::from([(agent.start, 0)]);
+
+    while let Some((point, t)) = queue.pop_front() {
+        observer.on_pop(point);
+        // На цели можно остаться, если через неё больше никто не пройдёт
+        let stays_free = reservations
+            .last_visit
+            .get(&point)
+            .is_none_or(|&last| last <= t);
+        if point == agent.goal && stays_free {
+            let mut path = vec![point];
+            let mut state = (point, t);
+            while let Some(&parent) = parents.get(&state) {
+                state = (parent, state.1 - 1);
+                path.push(parent);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if !observer.keep_going() {
+            return None;
+        }
+        if t >= horizon {
+            continue;
+        }
+
+        let mut moves = map.get_neighbors(point);
+        moves.push(point);
+        for next in moves {
+            let state = (next, t + 1);
+            if reservations.is_free(point, next, t + 1) && visited.insert(state) {
+                parents.insert(state, point);
+                queue.push_back(state);
+                observer.on_push(next);
+            }
+        }
+    }
+
+    None
+}
+
+/// Кадры анимации: карта в каждый момент времени с агентами,
+/// обозначенными символами 0-9, a-z, A-Z
+pub fn render_frames(map: &Map, paths: &[Vec<Point>]) -> Vec<String> {
+    let makespan = paths.iter().map(Vec::len).max().unwrap_or(0);
+    let base: Vec<Vec<char>> = (0..map.rows)
+        .map(|x| map.row_cells(x).map(Cell::to_char).collect())
+        .collect();
+
+    (0..makespan)
+        .map(|t| {
+            let mut frame = base.clone();
+            for (index, path) in paths.iter().enumerate() {
+                if let Some(point) = path.get(t).or(path.last()) {
+                    frame[point.x][point.y] =
+                        AGENT_SYMBOLS.get(index).map_or('*', |&ch| ch as char);
+                }
+            }
+            frame
+                .into_iter()
+                .map(|row| row.into_iter().collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect()
+}
@@ -0,0 +1,320 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::graph::{BudgetExceeded, Budgeted, NoObserver, SearchBudget, SearchObserver};
+use crate::map::{Cell, Connectivity, Map, Point};
+use crate::map_astar::STRAIGHT_COST;
+
+const INFINITY: u32 = u32::MAX;
+
+// Очередь не перестраивается, пока в ней меньше записей
+const MIN_COMPACT_LEN: usize = 1024;
+
+type Key = (u32, u32);
+
+// Запись очереди: ключ и номер клетки
+type Entry = Reverse<(Key, u32)>;
+
+/// Инкрементальный планировщик (LPA*) для фиксированных старта и финиша.
+/// Хранит состояние поиска между изменениями карты: после того как
+/// несколько клеток стали стенами или освободились, перерасчёт затрагивает
+/// только ту часть поиска, на которую эти изменения влияют.
+///
+/// Расчёт и перерасчёт идут в пределах бюджета. Перерасчёт, прерванный
+/// по бюджету, откатывается вместе с изменёнными клетками, и планировщик
+/// остаётся таким, каким был до `update_cells`.
+///
+/// g и rhs хранятся в плоских массивах по номерам клеток. Устаревшие
+/// записи очереди удаляются лениво, поэтому, когда их становится больше,
+/// чем несогласованных вершин, очередь перестраивается.
+#[derive(Debug, Clone)]
+pub struct Planner {
+    map: Map,
+    start: Point,
+    goal: Point,
+    g: Vec<u32>,
+    rhs: Vec<u32>,
+    queue: BinaryHeap<Entry>,
+    /// Сколько вершин сейчас несогласовано (g != rhs)
+    inconsistent: usize,
+    /// Журнал идущего перерасчёта; `None` вне `update_cells`
+    journal: Option<Journal>,
+    /// Сколько вершин раскрыл последний перерасчёт
+    pub expanded: usize,
+}
+
+// Прежние значения g и rhs в порядке изменения и извлечённые из очереди
+// записи - всё, что нужно, чтобы вернуть состояние до перерасчёта.
+// Записи, добавленные в очередь, откатывать не нужно: устаревшие
+// пропускаются при извлечении.
+#[derive(Debug, Clone, Default)]
+struct Journal {
+    g: Vec<(usize, u32)>,
+    rhs: Vec<(usize, u32)>,
+    popped: Vec<Entry>,
+}
+
+impl Planner {
+    pub fn new(
+        map: Map,
+        start: Point,
+        goal: Point,
+        budget: &SearchBudget,
+    ) -> Result<Self, BudgetExceeded> {
+        let mut planner = Planner {
+            g: vec![INFINITY; map.rows * map.cols],
+            rhs: vec![INFINITY; map.rows * map.cols],
+            queue: BinaryHeap::new(),
+            inconsistent: 0,
+            journal: None,
+            map,
+            start,
+            goal,
+            expanded: 0,
+        };
+
+        planner.set_rhs(planner.index(start), 0);
+        planner.push(start);
+        planner.compute_shortest_path(budget)?;
+        planner.compact_queue();
+        Ok(planner)
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    pub fn start(&self) -> Point {
+        self.start
+    }
+
+    pub fn goal(&self) -> Point {
+        self.goal
+    }
+
+    /// Число клеток карты - по нему ограничивается память сессий
+    pub fn cells(&self) -> usize {
+        self.g.len()
+    }
+
+    /// Меняет клетки (`true` - стена) и чинит кратчайший путь.
+    /// Клетки старта и финиша менять нельзя. Если бюджета не хватило,
+    /// клетки и состояние поиска возвращаются к прежним.
+    pub fn update_cells(
+        &mut self,
+        changes: &[(Point, bool)],
+        budget: &SearchBudget,
+    ) -> Result<(), BudgetExceeded> {
+        let previous: Vec<(Point, Cell)> = changes
+            .iter()
+            .map(|&(point, _)| (point, self.map.cell(point)))
+            .collect();
+        let expanded = self.expanded;
+        let inconsistent = self.inconsistent;
+        self.journal = Some(Journal::default());
+
+        for &(point, wall) in changes {
+            let cell = if wall { Cell::Wall } else { Cell::Empty };
+            self.map.set_cell(point, cell);
+        }
+        // Изменились рёбра, ведущие в клетку и из неё
+        for &(point, _) in changes {
+            self.update_vertex(point);
+            for neighbor in self.map.adjacent(point) {
+                self.update_vertex(neighbor);
+            }
+        }
+        let result = self.compute_shortest_path(budget);
+
+        let journal = self.journal.take().unwrap_or_default();
+        if result.is_err() {
+            for &(point, cell) in previous.iter().rev() {
+                self.map.set_cell(point, cell);
+            }
+            for &(index, g) in journal.g.iter().rev() {
+                self.g[index] = g;
+            }
+            for &(index, rhs) in journal.rhs.iter().rev() {
+                self.rhs[index] = rhs;
+            }
+            self.queue.extend(journal.popped);
+            self.inconsistent = inconsistent;
+            self.expanded = expanded;
+        }
+        // Вне журнала: выброшенные записи не понадобятся для отката
+        self.compact_queue();
+        result
+    }
+
+    /// Текущий кратчайший путь от старта до финиша
+    pub fn path(&self) -> Option<Vec<Point>> {
+        if self.g[self.index(self.goal)] == INFINITY {
+            return None;
+        }
+
+        // Спускаемся от финиша по соседям с наименьшим g
+        let mut path = vec![self.goal];
+        let mut current = self.goal;
+        while current != self.start {
+            current = self
+                .map
+                .adjacent(current)
+                .into_iter()
+                .filter(|&neighbor| self.cost(neighbor, current) != INFINITY)
+                .filter(|&neighbor| self.g[self.index(neighbor)] != INFINITY)
+                .min_by_key(|&neighbor| self.g[self.index(neighbor)])?;
+            path.push(current);
+            if path.len() > self.map.rows * self.map.cols {
+                return None;
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn compute_shortest_path(&mut self, budget: &SearchBudget) -> Result<(), BudgetExceeded> {
+        self.expanded = 0;
+        let mut inner = NoObserver;
+        let mut observer = Budgeted::new(budget, &mut inner);
+
+        let goal = self.index(self.goal);
+        while let Some(&Reverse((key, index))) = self.queue.peek() {
+            let goal_key = self.key(self.goal);
+            let goal_consistent = self.g[goal] == self.rhs[goal];
+            if key >= goal_key && goal_consistent {
+                break;
+            }
+            if let Some(popped) = self.queue.pop() {
+                if let Some(journal) = &mut self.journal {
+                    journal.popped.push(popped);
+                }
+            }
+
+            // Устаревшие записи очереди пропускаем
+            let index = index as usize;
+            let point = self.point(index);
+            if !self.is_live(key, index) {
+                continue;
+            }
+            self.expanded += 1;
+
+            if self.g[index] > self.rhs[index] {
+                self.set_g(index, self.rhs[index]);
+            } else {
+                self.set_g(index, INFINITY);
+                self.update_vertex(point);
+            }
+            for neighbor in self.map.adjacent(point) {
+                self.update_vertex(neighbor);
+            }
+
+            observer.on_pop(point);
+            if !SearchObserver::<Point>::keep_going(&observer) {
+                break;
+            }
+        }
+        observer.finish(())
+    }
+
+    fn update_vertex(&mut self, point: Point) {
+        let index = self.index(point);
+        if point != self.start {
+            let rhs = self
+                .map
+                .adjacent(point)
+                .into_iter()
+                .map(|neighbor| {
+                    self.g[self.index(neighbor)].saturating_add(self.cost(neighbor, point))
+                })
+                .min()
+                .unwrap_or(INFINITY);
+            self.set_rhs(index, rhs);
+        }
+        if self.g[index] != self.rhs[index] {
+            self.push(point);
+        }
+    }
+
+    fn push(&mut self, point: Point) {
+        let key = self.key(point);
+        self.queue.push(Reverse((key, self.index(point) as u32)));
+    }
+
+    // Запись очереди актуальна, если вершина несогласована и ключ не менялся
+    fn is_live(&self, key: Key, index: usize) -> bool {
+        self.g[index] != self.rhs[index] && key == self.key(self.point(index))
+    }
+
+    // Перестраивает очередь без устаревших записей, когда их больше, чем
+    // актуальных. На каждую несогласованную вершину остаётся одна запись
+    fn compact_queue(&mut self) {
+        let len = self.queue.len();
+        if len < MIN_COMPACT_LEN || len <= 2 * self.inconsistent {
+            return;
+        }
+        let mut entries = std::mem::take(&mut self.queue).into_vec();
+        entries.retain(|&Reverse((key, index))| self.is_live(key, index as usize));
+        entries.sort_unstable();
+        entries.dedup();
+        self.queue = BinaryHeap::from(entries);
+    }
+
+    fn set_g(&mut self, index: usize, g: u32) {
+        if let Some(journal) = &mut self.journal {
+            journal.g.push((index, self.g[index]));
+        }
+        let was_consistent = self.g[index] == self.rhs[index];
+        self.g[index] = g;
+        self.track_consistency(index, was_consistent);
+    }
+
+    fn set_rhs(&mut self, index: usize, rhs: u32) {
+        if let Some(journal) = &mut self.journal {
+            journal.rhs.push((index, self.rhs[index]));
+        }
+        let was_consistent = self.g[index] == self.rhs[index];
+        self.rhs[index] = rhs;
+        self.track_consistency(index, was_consistent);
+    }
+
+    fn track_consistency(&mut self, index: usize, was_consistent: bool) {
+        match (was_consistent, self.g[index] == self.rhs[index]) {
+            (true, false) => self.inconsistent += 1,
+            (false, true) => self.inconsistent -= 1,
+            _ => {}
+        }
+    }
+
+    /// Число записей в очереди, включая устаревшие
+    #[allow(dead_code)] // используется в тестах
+    pub fn queue_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    fn index(&self, point: Point) -> usize {
+        point.x * self.map.cols + point.y
+    }
+
+    fn point(&self, index: usize) -> Point {
+        Point {
+            x: index / self.map.cols,
+            y: index % self.map.cols,
+        }
+    }
+
+    fn key(&self, point: Point) -> Key {
+        let index = self.index(point);
+        let value = self.g[index].min(self.rhs[index]);
+        let heuristic = self.map.heuristic(point, self.goal, Connectivity::Four) / STRAIGHT_COST;
+        (value.saturating_add(heuristic), value)
+    }
+
+    // Цена шага между соседними клетками: проходимы обе - 1, иначе бесконечность
+    fn cost(&self, from: Point, to: Point) -> u32 {
+        if self.map.is_open(from) && self.map.is_open(to) {
+            1
+        } else {
+            INFINITY
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::planner::Planner;
+
+// Limits of the incremental planning sessions
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    // How long a session lives after its last use
+    pub ttl: Duration,
+    // Sessions alive at the same time; further ones are rejected
+    pub max_sessions: usize,
+    // Map cells of all open sessions together. A planner keeps about a
+    // dozen bytes per cell, so this bounds their memory rather than the count
+    pub max_cells: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            ttl: Duration::from_secs(30 * 60),
+            max_sessions: 256,
+            max_cells: 16 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CreateError {
+    TooManySessions,
+}
+
+// A planner is locked on its own, so a long update does not hold up the
+// other sessions
+pub type SharedPlanner = Arc<Mutex<Planner>>;
+
+struct Session {
+    planner: SharedPlanner,
+    cells: usize,
+    last_used: Instant,
+}
+
+// Each session holds a copy of its map and the planner state, so their
+// number and total size are capped and idle ones are dropped lazily on the
+// next access
+#[derive(Clone)]
+pub struct SessionStore {
+    sessions: Arc<Mutex<HashMap<Uuid, Session>>>,
+    config: SessionConfig,
+}
+
+impl SessionStore {
+    pub fn new(config: SessionConfig) -> Self {
+        SessionStore {
+            sessions: Arc::default(),
+            config,
+        }
+    }
+
+    pub async fn insert(&self, id: Uuid, planner: Planner) -> Result<(), CreateError> {
+        let mut sessions = self.lock().await;
+        let cells = planner.cells();
+        let open_cells: usize = sessions.values().map(|session| session.cells).sum();
+        if sessions.len() >= self.config.max_sessions
+            || open_cells.saturating_add(cells) > self.config.max_cells
+        {
+            return Err(CreateError::TooManySessions);
+        }
+
+        sessions.insert(
+            id,
+            Session {
+                planner: Arc::new(Mutex::new(planner)),
+                cells,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    // The session's planner; counts as a use
+    pub async fn get(&self, id: Uuid) -> Option<SharedPlanner> {
+        let mut sessions = self.lock().await;
+        let session = sessions.get_mut(&id)?;
+        session.last_used = Instant::now();
+        Some(session.planner.clone())
+    }
+
+    pub async fn remove(&self, id: Uuid) -> bool {
+        self.lock().await.remove(&id).is_some()
+    }
+
+    async fn lock(&self) -> tokio::sync::MutexGuard<'_, HashMap<Uuid, Session>> {
+        let mut sessions = self.sessions.lock().await;
+        let ttl = self.config.ttl;
+        sessions.retain(|_, session| session.last_used.elapsed() < ttl);
+        sessions
+    }
+}
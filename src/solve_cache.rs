@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::map::{Algorithm, Connectivity, Point};
+
+// Least recently used cache with hit and miss counters, bounded by the
+// total size of its entries in bytes as given on insertion. Recency is kept
+// as a tick per entry plus an ordered index from tick to key, so both
+// lookups and evictions take logarithmic time.
+pub struct LruCache<K, V> {
+    capacity: usize,
+    bytes: usize,
+    // Value, tick of the last use and size in bytes
+    entries: HashMap<K, (V, u64, usize)>,
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            bytes: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let Some((_, used, _)) = self.entries.get_mut(key) else {
+            self.misses += 1;
+            return None;
+        };
+        self.hits += 1;
+        self.tick += 1;
+        self.order.remove(used);
+        self.order.insert(self.tick, key.clone());
+        *used = self.tick;
+        self.entries.get(key).map(|(value, _, _)| value)
+    }
+
+    // Evicts the least recently used entries until the new one fits; an
+    // entry larger than the whole capacity is not kept
+    pub fn insert(&mut self, key: K, value: V, bytes: usize) {
+        if let Some((_, used, old_bytes)) = self.entries.remove(&key) {
+            self.order.remove(&used);
+            self.bytes -= old_bytes;
+        }
+        if bytes > self.capacity {
+            return;
+        }
+        while self.bytes + bytes > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((_, _, old_bytes)) = self.entries.remove(&oldest) {
+                self.bytes -= old_bytes;
+            }
+        }
+        self.tick += 1;
+        self.bytes += bytes;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick, bytes));
+    }
+
+    // Drops every entry whose key does not pass the filter
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        let entries = &mut self.entries;
+        let bytes = &mut self.bytes;
+        self.order.retain(|_, key| {
+            let kept = keep(key);
+            if !kept {
+                if let Some((_, _, old_bytes)) = entries.remove(key) {
+                    *bytes -= old_bytes;
+                }
+            }
+            kept
+        });
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+            bytes: self.bytes,
+            capacity: self.capacity,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    // Size of the entries and its upper bound
+    pub bytes: usize,
+    pub capacity: usize,
+}
+
+// Everything a cached solve depends on. The map version changes on every
+// edit, so a stale entry can never be hit; invalidation only frees memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SolveKey {
+    pub map_id: Uuid,
+    pub version: u64,
+    pub start: Point,
+    pub finish: Point,
+    pub algorithm: Algorithm,
+    pub connectivity: Connectivity,
+    pub any_angle: bool,
+}
+
+// Found path, or `None` if there is none
+pub type CachedPath = Option<Vec<Point>>;
+
+// Solve results shared by all requests. Only the paths are kept; the
+// response is rendered from the stored map on every hit, so an entry costs
+// the size of its path rather than of the whole map.
+#[derive(Clone)]
+pub struct SolveCache(Arc<Mutex<LruCache<SolveKey, CachedPath>>>);
+
+impl SolveCache {
+    // Capacity in bytes
+    pub fn new(capacity: usize) -> Self {
+        SolveCache(Arc::new(Mutex::new(LruCache::new(capacity))))
+    }
+
+    pub fn get(&self, key: &SolveKey) -> Option<CachedPath> {
+        self.0.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: SolveKey, path: CachedPath) {
+        let bytes = size_of::<(SolveKey, CachedPath)>()
+            + path.as_ref().map_or(0, |path| size_of_val(path.as_slice()));
+        self.0.lock().unwrap().insert(key, path, bytes);
+    }
+
+    // Forgets all results for the map, after it was edited or deleted
+    pub fn invalidate_map(&self, map_id: Uuid) {
+        self.0.lock().unwrap().retain(|key| key.map_id != map_id);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.0.lock().unwrap().stats()
+    }
+}
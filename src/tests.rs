@@ -1,7 +1,23 @@
 #[cfg(test)]
 mod tests {
-    use crate::map::{Cell, Map, Point};
-    use crate::map_parser::ParsePointError;
+    use crate::bulk::{self, BulkBudget, BulkInput, BulkItem, BulkOperation};
+    use crate::graph::{
+        self, BudgetExceeded, Budgeted, CancelToken, Graph, NoObserver, SearchBudget,
+        SearchObserver, TraceEvent, TraceRecorder,
+    };
+    use crate::hpa::Hierarchy;
+    use crate::jobs::{JobConfig, JobQueue, JobStatus, SubmitError};
+    use crate::layered::{LayeredMap, Point3, Wrap};
+    use crate::map::{Algorithm, Cell, Connectivity, GridKind, Map, Point, SearchOptions};
+    use crate::map_components::BoundingBox;
+    use crate::map_constraints::{ConstrainedPath, Constraint, PathConstraints, Region};
+    use crate::map_formatter::distance_matrix_csv;
+    use crate::map_parser::{MapLimits, ParseMapError, ParsePointError};
+    use crate::map_pursuit::TargetPolicy;
+    use crate::metrics;
+    use crate::multi_agent::{self, Agent, PlanError};
+    use crate::planner::Planner;
+    use crate::solve_cache::{CacheStats, LruCache};
     use std::str::FromStr;
 
     #[test]
@@ -26,7 +42,7 @@ mod tests {
         let map = Map::new();
         assert_eq!(map.rows, 0);
         assert_eq!(map.cols, 0);
-        assert!(map.grid.is_empty());
+        assert_eq!(map.find_cell(Cell::Empty), None);
     }
 
     #[test]
@@ -36,12 +52,12 @@ mod tests {
 
         assert_eq!(map.rows, 2);
         assert_eq!(map.cols, 3);
-        assert_eq!(map.grid[0][0], Cell::Wall);
-        assert_eq!(map.grid[0][1], Cell::Empty);
-        assert_eq!(map.grid[0][2], Cell::Wall);
-        assert_eq!(map.grid[1][0], Cell::Empty);
-        assert_eq!(map.grid[1][1], Cell::Wall);
-        assert_eq!(map.grid[1][2], Cell::Empty);
+        assert_eq!(map.cell(Point { x: 0, y: 0 }), Cell::Wall);
+        assert_eq!(map.cell(Point { x: 0, y: 1 }), Cell::Empty);
+        assert_eq!(map.cell(Point { x: 0, y: 2 }), Cell::Wall);
+        assert_eq!(map.cell(Point { x: 1, y: 0 }), Cell::Empty);
+        assert_eq!(map.cell(Point { x: 1, y: 1 }), Cell::Wall);
+        assert_eq!(map.cell(Point { x: 1, y: 2 }), Cell::Empty);
     }
 
     #[test]
@@ -70,10 +86,7 @@ mod tests {
 
     #[test]
     fn test_find_and_mark_path() {
-        let mut map = Map::new();
-        map.rows = 3;
-        map.cols = 3;
-        map.grid = vec![vec![Cell::Empty; 3]; 3];
+        let mut map = Map::with_size(3, 3);
 
         let start = Point { x: 0, y: 0 };
         let end = Point { x: 2, y: 2 };
@@ -81,26 +94,22 @@ mod tests {
         let result = map.find_and_mark_path(start, end);
         assert!(result);
 
-        assert_eq!(map.grid[0][0], Cell::Start);
-        assert_eq!(map.grid[2][2], Cell::End);
+        assert_eq!(map.cell(Point { x: 0, y: 0 }), Cell::Start);
+        assert_eq!(map.cell(Point { x: 2, y: 2 }), Cell::End);
         // At least one path cell should be marked
-        let path_cells_count = map
-            .grid
-            .iter()
-            .flat_map(|row| row.iter())
-            .filter(|&&cell| cell == Cell::Path)
+        let path_cells_count = (0..map.rows)
+            .flat_map(|x| map.row_cells(x))
+            .filter(|&cell| cell == Cell::Path)
             .count();
         assert!(path_cells_count > 0);
     }
 
     #[test]
     fn test_find_and_mark_path_no_path() {
-        let mut map = Map::new();
-        map.rows = 3;
-        map.cols = 3;
-        map.grid = vec![vec![Cell::Wall; 3]; 3];
-        map.grid[0][0] = Cell::Empty;
-        map.grid[2][2] = Cell::Empty;
+        let mut grid = vec![vec![Cell::Wall; 3]; 3];
+        grid[0][0] = Cell::Empty;
+        grid[2][2] = Cell::Empty;
+        let mut map = Map::from_grid(&grid);
 
         let start = Point { x: 0, y: 0 };
         let end = Point { x: 2, y: 2 };
@@ -109,16 +118,13 @@ mod tests {
         assert!(!result);
 
         // Grid should remain unchanged
-        assert_eq!(map.grid[0][0], Cell::Empty);
-        assert_eq!(map.grid[2][2], Cell::Empty);
+        assert_eq!(map.cell(Point { x: 0, y: 0 }), Cell::Empty);
+        assert_eq!(map.cell(Point { x: 2, y: 2 }), Cell::Empty);
     }
 
     #[test]
     fn test_map_display() {
-        let mut map = Map::new();
-        map.rows = 2;
-        map.cols = 2;
-        map.grid = vec![vec![Cell::Wall, Cell::Empty], vec![Cell::Empty, Cell::Wall]];
+        let map = Map::from_grid(&[vec![Cell::Wall, Cell::Empty], vec![Cell::Empty, Cell::Wall]]);
 
         let display_output = format!("{}", map);
         let expected = "# \n #";
@@ -138,7 +144,2377 @@ mod tests {
         assert!(result);
 
         // Verify start and end are marked
-        assert_eq!(map.grid[0][1], Cell::Start);
-        assert_eq!(map.grid[2][1], Cell::End);
+        assert_eq!(map.cell(Point { x: 0, y: 1 }), Cell::Start);
+        assert_eq!(map.cell(Point { x: 2, y: 1 }), Cell::End);
+    }
+
+    #[test]
+    fn test_point_from_str() {
+        assert_eq!(Point::from_str("1,2").unwrap(), Point { x: 1, y: 2 });
+        assert_eq!(Point::from_str(" 3 , 4 ").unwrap(), Point { x: 3, y: 4 });
+        assert_eq!(Point::from_str("1;2").unwrap_err(), ParsePointError);
+        assert_eq!(Point::from_str("-1,2").unwrap_err(), ParsePointError);
+    }
+
+    #[test]
+    fn test_distances_from_wraps_around() {
+        let map = Map::from_str("     \n# ###").unwrap();
+
+        let distances = map.distances_from(Point { x: 0, y: 0 });
+
+        assert_eq!(
+            distances[0],
+            vec![Some(0), Some(1), Some(2), Some(2), Some(1)]
+        );
+        assert_eq!(distances[1], vec![None, Some(2), None, None, None]);
+    }
+
+    #[test]
+    fn test_distances_from_unreachable_cells() {
+        let map = Map::from_str(" # \n###\n # ").unwrap();
+
+        let distances = map.distances_from(Point { x: 0, y: 0 });
+
+        assert_eq!(distances[0][0], Some(0));
+        assert_eq!(distances[0][2], Some(1));
+        assert_eq!(distances[2][0], Some(1));
+        assert_eq!(distances[2][2], Some(2));
+        assert!(distances[1].iter().all(|d| d.is_none()));
+    }
+
+    #[test]
+    fn test_render_heatmap() {
+        let map = Map::from_str("  # \n####").unwrap();
+
+        let distances = map.distances_from(Point { x: 0, y: 1 });
+
+        assert_eq!(map.render_heatmap(&distances), "10#2\n####");
+    }
+
+    #[test]
+    fn test_map_parsing_declared_endpoints() {
+        let map = Map::from_str("#i #\n# O#").unwrap();
+
+        assert_eq!(map.cell(Point { x: 0, y: 1 }), Cell::Start);
+        assert_eq!(map.cell(Point { x: 1, y: 2 }), Cell::End);
+        assert_eq!(map.find_cell(Cell::Start), Some(Point { x: 0, y: 1 }));
+        assert_eq!(map.find_cell(Cell::End), Some(Point { x: 1, y: 2 }));
+        assert_eq!(map.find_cell(Cell::Path), None);
+        assert_eq!(map.to_string(), "#i #\n# O#");
+    }
+
+    #[test]
+    fn test_connected_components_across_seam() {
+        // Левый и правый края соединяются через шов тора
+        let map = Map::from_str(" # \n###\n # ").unwrap();
+
+        let components = map.connected_components();
+
+        assert!(components.is_connected());
+        assert_eq!(components.components.len(), 1);
+        assert_eq!(components.components[0].size, 4);
+        assert_eq!(
+            components.components[0].bounding_box,
+            BoundingBox {
+                x: 2,
+                y: 2,
+                rows: 2,
+                cols: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_connected_components_isolated_pocket() {
+        let map = Map::from_str("#####\n#  ##\n#####\n## # \n#####").unwrap();
+
+        let components = map.connected_components();
+
+        assert!(!components.is_connected());
+        assert_eq!(components.components.len(), 3);
+        assert_eq!(components.components[0].size, 2);
+        assert_eq!(components.component_of(Point { x: 1, y: 2 }), Some(0));
+        assert_eq!(components.component_of(Point { x: 3, y: 2 }), Some(1));
+        assert_eq!(components.component_of(Point { x: 3, y: 4 }), Some(2));
+        assert_eq!(components.component_of(Point { x: 0, y: 0 }), None);
+    }
+
+    #[test]
+    fn test_connected_components_checkerboard() {
+        // Каждая открытая клетка шахматной доски - отдельная компонента
+        let text = (0..6)
+            .map(|x| {
+                (0..6)
+                    .map(|y| if (x + y) % 2 == 0 { ' ' } else { '#' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let map = Map::from_str(&text).unwrap();
+
+        let components = map.connected_components();
+
+        assert_eq!(components.components.len(), 18);
+        assert!(components.components.iter().all(|component| {
+            let cell = component.bounding_box;
+            component.size == 1
+                && (cell.rows, cell.cols) == (1, 1)
+                && components.component_of(Point {
+                    x: cell.x,
+                    y: cell.y,
+                }) == Some(component.id)
+        }));
+
+        let budget = SearchBudget {
+            max_expanded: Some(5),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            map.connected_components_budgeted(&budget, &mut NoObserver),
+            Err(BudgetExceeded::Expanded)
+        );
+        assert_eq!(
+            map.connected_components_budgeted(&SearchBudget::default(), &mut NoObserver),
+            Ok(components)
+        );
+    }
+
+    #[test]
+    fn test_metrics_perfect_maze() {
+        let map = Map::from_str("#####\n#   #\n## ##\n## ##\n#####").unwrap();
+
+        let metrics = metrics::compute(&map, Some((Point { x: 1, y: 1 }, Point { x: 3, y: 2 })));
+
+        assert_eq!(metrics.open_cells, 5);
+        assert_eq!(metrics.dead_ends, 3);
+        assert_eq!(metrics.junctions, 1);
+        assert_eq!(metrics.branching_factor, 2.0);
+        assert_eq!(metrics.corridor_lengths.get(&1), Some(&1));
+        assert_eq!(metrics.diameter, 3);
+        assert_eq!(metrics.solution_length, Some(3));
+        assert_eq!(metrics.solution_ratio, Some(3.0 / 25.0));
+        assert!(metrics.is_perfect);
+        assert!(metrics.difficulty > 0.0 && metrics.difficulty <= 100.0);
+    }
+
+    #[test]
+    fn test_metrics_loop_is_not_perfect() {
+        let map = Map::from_str("#####\n#   #\n# # #\n#   #\n#####").unwrap();
+
+        let metrics = metrics::compute(&map, None);
+
+        assert_eq!(metrics.open_cells, 8);
+        assert_eq!(metrics.dead_ends, 0);
+        assert_eq!(metrics.junctions, 0);
+        assert_eq!(metrics.river, 1.0);
+        assert_eq!(metrics.corridor_lengths.get(&8), Some(&1));
+        assert_eq!(metrics.diameter, 4);
+        assert_eq!(metrics.solution_length, None);
+        assert!(!metrics.is_perfect);
+
+        // На карте с циклом диаметр ищется обходом из каждой клетки
+        let small = SearchBudget {
+            max_expanded: Some(10),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            metrics::compute_budgeted(&map, None, &small, &mut NoObserver),
+            Err(BudgetExceeded::Expanded)
+        );
+    }
+
+    #[test]
+    fn test_metrics_narrow_map_counts_distinct_neighbors() {
+        // На карте в одну строку верх и низ ведут в ту же клетку
+        let map = Map::from_str("   ").unwrap();
+
+        let metrics = metrics::compute(&map, None);
+
+        assert_eq!(metrics.dead_ends, 0);
+        assert_eq!(metrics.corridor_lengths.get(&3), Some(&1));
+        assert!(!metrics.is_perfect);
+    }
+
+    // Диаметр перебором: BFS из каждой открытой клетки
+    fn brute_force_diameter(map: &Map) -> usize {
+        (0..map.rows)
+            .flat_map(|x| (0..map.cols).map(move |y| Point { x, y }))
+            .filter(|&point| map.is_open(point))
+            .filter_map(|point| {
+                map.distances_from(point)
+                    .rows()
+                    .flatten()
+                    .flatten()
+                    .max()
+                    .map(|&d| d as usize)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_diameter_of_tree_matches_brute_force() {
+        let input = "#######\n#   # #\n# # # #\n# #   #\n# ### #\n#     #\n#######";
+        let map = Map::from_str(input).unwrap();
+
+        let diameter = map.diameter().unwrap();
+
+        assert_eq!(diameter.length, brute_force_diameter(&map));
+        assert_eq!(
+            map.distances_from(diameter.start).get(diameter.finish),
+            Some(diameter.length)
+        );
+    }
+
+    #[test]
+    fn test_diameter_with_loops_and_wrap() {
+        let map = Map::from_str("     \n # # \n     ").unwrap();
+
+        let diameter = map.diameter().unwrap();
+
+        assert_eq!(diameter.length, brute_force_diameter(&map));
+        assert!(Map::from_str("###").unwrap().diameter().is_none());
+
+        // С циклами нужен обход из каждой из 13 клеток
+        assert_eq!(
+            map.diameter_budgeted(&SearchBudget::default(), &mut NoObserver),
+            Ok(Some(diameter))
+        );
+        let small = SearchBudget {
+            max_expanded: Some(20),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            map.diameter_budgeted(&small, &mut NoObserver),
+            Err(BudgetExceeded::Expanded)
+        );
+    }
+
+    #[test]
+    fn test_place_endpoints_replaces_markers() {
+        let mut map = Map::from_str("#####\n#i O#\n## ##\n## ##\n#####").unwrap();
+
+        let diameter = map.place_endpoints().unwrap();
+
+        assert_eq!(diameter.length, 3);
+        assert_eq!(map.cell(diameter.start), Cell::Start);
+        assert_eq!(map.cell(diameter.finish), Cell::End);
+        let markers = (0..map.rows)
+            .flat_map(|x| map.row_cells(x))
+            .filter(|&cell| cell == Cell::Start || cell == Cell::End)
+            .count();
+        assert_eq!(markers, 2);
+
+        // Лишние старты и финиши убираются за один проход, путь остаётся
+        let mut marked = Map::with_size(3, 8);
+        for y in 0..8 {
+            let marker = [Cell::Start, Cell::End, Cell::Path][y % 3];
+            marked.set_cell(Point { x: 1, y }, marker);
+        }
+        marked.clear_markers(&[Cell::Start, Cell::End]);
+        assert_eq!(marked.find_cell(Cell::Start), None);
+        assert_eq!(marked.find_cell(Cell::End), None);
+        assert_eq!(marked.to_string(), "        \n  .  .  \n        ");
+    }
+
+    // Длина найденного пути в шагах; проверяет, что соседние клетки пути смежны
+    fn path_length(map: &Map, path: &[Point]) -> usize {
+        for pair in path.windows(2) {
+            assert!(map.get_neighbors(pair[0]).contains(&pair[1]));
+        }
+        path.len() - 1
+    }
+
+    #[test]
+    fn test_bidirectional_bfs_matches_bfs() {
+        let input = "#######\n#   # #\n# # # #\n# #   #\n# ### #\n#     #\n#######";
+        let map = Map::from_str(input).unwrap();
+        let start = Point { x: 1, y: 1 };
+
+        for finish in [
+            Point { x: 1, y: 1 },
+            Point { x: 1, y: 2 },
+            Point { x: 1, y: 5 },
+            Point { x: 5, y: 5 },
+            Point { x: 3, y: 4 },
+        ] {
+            let bfs = map.search(start, finish, Algorithm::Bfs).path.unwrap();
+            let bidirectional = map
+                .search(start, finish, Algorithm::BidirectionalBfs)
+                .path
+                .unwrap();
+
+            assert_eq!(bidirectional.first(), Some(&start));
+            assert_eq!(bidirectional.last(), Some(&finish));
+            assert_eq!(path_length(&map, &bidirectional), path_length(&map, &bfs));
+        }
+    }
+
+    #[test]
+    fn test_bidirectional_bfs_no_path() {
+        let map = Map::from_str(" # #\n####").unwrap();
+
+        let result = map.search(
+            Point { x: 0, y: 0 },
+            Point { x: 0, y: 2 },
+            Algorithm::BidirectionalBfs,
+        );
+
+        assert_eq!(result.path, None);
+    }
+
+    #[test]
+    fn test_bidirectional_bfs_expands_fewer_cells() {
+        let map = Map::from_str(&vec![" ".repeat(200); 200].join("\n")).unwrap();
+        let start = Point { x: 0, y: 0 };
+        let finish = Point { x: 40, y: 40 };
+
+        let bfs = map.search(start, finish, Algorithm::Bfs);
+        let bidirectional = map.search(start, finish, Algorithm::BidirectionalBfs);
+
+        assert_eq!(bidirectional.path.as_ref().unwrap().len(), 81);
+        assert!(bidirectional.expanded * 3 < bfs.expanded * 2);
+    }
+
+    // Карта размера из запроса: 2000 x 2000 клеток с редкими стенами
+    #[test]
+    fn test_bidirectional_bfs_on_4_megacells() {
+        let size = 2000;
+        let mut map = Map::with_size(size, size);
+        for x in (0..size).step_by(7) {
+            for y in (0..size).step_by(5) {
+                map.set_cell(Point { x, y }, Cell::Wall);
+            }
+        }
+        let start = Point { x: 1, y: 1 };
+        let finish = Point {
+            x: size / 4,
+            y: size / 4 + 1,
+        };
+
+        let bfs = map.search(start, finish, Algorithm::Bfs);
+        let bidirectional = map.search(start, finish, Algorithm::BidirectionalBfs);
+
+        let bfs_path = bfs.path.unwrap();
+        let bidirectional_path = bidirectional.path.unwrap();
+        assert_eq!(bidirectional_path.first(), Some(&start));
+        assert_eq!(bidirectional_path.last(), Some(&finish));
+        assert_eq!(bidirectional_path.len(), bfs_path.len());
+        // Две встречные волны радиусом вдвое меньше
+        assert!(bidirectional.expanded * 3 < bfs.expanded * 2);
+    }
+
+    // Простой линейный конгруэнтный генератор для воспроизводимых случайных карт
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) % bound as u64) as usize
+        }
+    }
+
+    fn random_map(rng: &mut Lcg, rows: usize, cols: usize, wall_percent: usize) -> Map {
+        let mut map = Map::with_size(rows, cols);
+        for x in 0..rows {
+            for y in 0..cols {
+                if rng.next(100) < wall_percent {
+                    map.set_cell(Point { x, y }, Cell::Wall);
+                }
+            }
+        }
+        map
+    }
+
+    fn random_open_point(rng: &mut Lcg, map: &Map) -> Option<Point> {
+        (0..50)
+            .map(|_| Point {
+                x: rng.next(map.rows),
+                y: rng.next(map.cols),
+            })
+            .find(|&point| map.validate_coordinates(point))
+    }
+
+    // Цена пути; заодно проверяет, что каждый ход допустим
+    fn path_cost(map: &Map, path: &[Point], connectivity: Connectivity) -> u32 {
+        path.windows(2)
+            .map(|pair| {
+                map.weighted_neighbors(pair[0], connectivity)
+                    .into_iter()
+                    .find(|&(neighbor, _)| neighbor == pair[1])
+                    .map(|(_, cost)| cost)
+                    .expect("path contains an illegal move")
+            })
+            .sum()
+    }
+
+    // Сравнивает стоимость путей всех алгоритмов на случайных картах
+    fn assert_algorithms_agree(connectivity: Connectivity, algorithms: &[Algorithm]) {
+        let mut rng = Lcg(connectivity as u64 + 7);
+
+        for _ in 0..400 {
+            let rows = 1 + rng.next(14);
+            let cols = 1 + rng.next(14);
+            let wall_percent = 15 + rng.next(30);
+            let map = random_map(&mut rng, rows, cols, wall_percent);
+            let (Some(start), Some(finish)) = (
+                random_open_point(&mut rng, &map),
+                random_open_point(&mut rng, &map),
+            ) else {
+                continue;
+            };
+
+            let reference = map
+                .search(
+                    start,
+                    finish,
+                    SearchOptions {
+                        algorithm: Algorithm::AStar,
+                        connectivity,
+                    },
+                )
+                .path
+                .map(|path| path_cost(&map, &path, connectivity));
+
+            for &algorithm in algorithms {
+                let path = map
+                    .search(
+                        start,
+                        finish,
+                        SearchOptions {
+                            algorithm,
+                            connectivity,
+                        },
+                    )
+                    .path;
+                if let Some(path) = &path {
+                    assert_eq!(path.first(), Some(&start));
+                    assert_eq!(path.last(), Some(&finish));
+                }
+                assert_eq!(
+                    path.map(|path| path_cost(&map, &path, connectivity)),
+                    reference,
+                    "{:?} on\n{}\nfrom {:?} to {:?}",
+                    algorithm,
+                    map,
+                    start,
+                    finish
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_jps_matches_bfs_and_astar_4_connected() {
+        assert_algorithms_agree(
+            Connectivity::Four,
+            &[
+                Algorithm::Bfs,
+                Algorithm::BidirectionalBfs,
+                Algorithm::Dijkstra,
+                Algorithm::Jps,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_jps_matches_astar_8_connected() {
+        assert_algorithms_agree(Connectivity::Eight, &[Algorithm::Dijkstra, Algorithm::Jps]);
+    }
+
+    #[test]
+    fn test_jps_open_arena_expands_fewer_nodes() {
+        let map = Map::from_str(&vec![" ".repeat(100); 100].join("\n")).unwrap();
+        let start = Point { x: 10, y: 10 };
+        let finish = Point { x: 40, y: 70 };
+
+        for connectivity in [Connectivity::Four, Connectivity::Eight] {
+            let options = |algorithm| SearchOptions {
+                algorithm,
+                connectivity,
+            };
+            let astar = map.search(start, finish, options(Algorithm::AStar));
+            let jps = map.search(start, finish, options(Algorithm::Jps));
+
+            assert_eq!(
+                path_cost(&map, &jps.path.unwrap(), connectivity),
+                path_cost(&map, &astar.path.unwrap(), connectivity)
+            );
+            assert!(jps.expanded < astar.expanded);
+        }
+    }
+
+    #[test]
+    fn test_hpa_finds_valid_paths_on_random_maps() {
+        let mut rng = Lcg(32);
+
+        for _ in 0..300 {
+            let rows = 1 + rng.next(30);
+            let cols = 1 + rng.next(30);
+            let wall_percent = 10 + rng.next(35);
+            let map = random_map(&mut rng, rows, cols, wall_percent);
+            let hierarchy = Hierarchy::with_cluster_size(&map, 2 + rng.next(6));
+            let (Some(start), Some(finish)) = (
+                random_open_point(&mut rng, &map),
+                random_open_point(&mut rng, &map),
+            ) else {
+                continue;
+            };
+
+            let bfs = map.search(start, finish, Algorithm::Bfs).path;
+            let hpa = hierarchy.find_path(&map, start, finish).path;
+
+            assert_eq!(
+                hpa.is_some(),
+                bfs.is_some(),
+                "on\n{}\nfrom {:?} to {:?}",
+                map,
+                start,
+                finish
+            );
+            if let (Some(hpa), Some(bfs)) = (hpa, bfs) {
+                assert_eq!(hpa.first(), Some(&start));
+                assert_eq!(hpa.last(), Some(&finish));
+                assert!(path_length(&map, &hpa) >= path_length(&map, &bfs));
+            }
+        }
+    }
+
+    #[test]
+    fn test_hpa_build_budget_and_cache() {
+        let map = Map::from_str(&vec![" ".repeat(45); 35].join("\n")).unwrap();
+        let start = Point { x: 2, y: 3 };
+        let finish = Point { x: 20, y: 30 };
+
+        // Сборку ограничивают только время и отмена
+        let late = SearchBudget {
+            deadline: Some(std::time::Instant::now()),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            Hierarchy::build_budgeted(&map, &late),
+            Err(BudgetExceeded::Deadline)
+        );
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let cancelled = SearchBudget {
+            cancel: Some(cancel),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            Hierarchy::build_budgeted(&map, &cancelled),
+            Err(BudgetExceeded::Cancelled)
+        );
+        let few_steps = SearchBudget {
+            max_expanded: Some(1),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            Hierarchy::build_budgeted(&map, &few_steps),
+            Ok(Hierarchy::build(&map))
+        );
+
+        // Прерванная сборка ничего не кэширует, удачная - переиспользуется
+        let cached = std::sync::OnceLock::new();
+        assert_eq!(
+            map.search_cached(
+                start,
+                finish,
+                Algorithm::Hpa,
+                &cached,
+                &late,
+                &mut NoObserver
+            ),
+            Err(BudgetExceeded::Deadline)
+        );
+        assert!(cached.get().is_none());
+        let result = map
+            .search_cached(
+                start,
+                finish,
+                Algorithm::Hpa,
+                &cached,
+                &SearchBudget::default(),
+                &mut NoObserver,
+            )
+            .unwrap();
+        assert_eq!(cached.get(), Some(&Hierarchy::build(&map)));
+        assert_eq!(result, map.search(start, finish, Algorithm::Hpa));
+    }
+
+    #[test]
+    fn test_hpa_is_optimal_on_open_map() {
+        let map = Map::from_str(&vec![" ".repeat(45); 35].join("\n")).unwrap();
+        let hierarchy = Hierarchy::build(&map);
+        let start = Point { x: 2, y: 3 };
+        let finish = Point { x: 20, y: 30 };
+
+        let path = hierarchy.find_path(&map, start, finish).path.unwrap();
+
+        // По строкам короче через шов: 35 - 18 = 17
+        assert_eq!(path_length(&map, &path), 17 + 18);
+    }
+
+    #[test]
+    fn test_planner_repairs_path_after_cell_changes() {
+        let mut rng = Lcg(33);
+
+        for _ in 0..100 {
+            let rows = 1 + rng.next(15);
+            let cols = 1 + rng.next(15);
+            let wall_percent = 10 + rng.next(30);
+            let map = random_map(&mut rng, rows, cols, wall_percent);
+            let (Some(start), Some(goal)) = (
+                random_open_point(&mut rng, &map),
+                random_open_point(&mut rng, &map),
+            ) else {
+                continue;
+            };
+            let mut planner = Planner::new(map, start, goal, &SearchBudget::default()).unwrap();
+
+            for _ in 0..10 {
+                let count = 1 + rng.next(3);
+                let mut changes = Vec::new();
+                for _ in 0..count {
+                    let point = Point {
+                        x: rng.next(rows),
+                        y: rng.next(cols),
+                    };
+                    if point != start && point != goal {
+                        changes.push((point, rng.next(2) == 0));
+                    }
+                }
+                planner
+                    .update_cells(&changes, &SearchBudget::default())
+                    .unwrap();
+
+                let expected = planner.map().search(start, goal, Algorithm::Bfs).path;
+                let actual = planner.path();
+                assert_eq!(actual.is_some(), expected.is_some());
+                if let (Some(actual), Some(expected)) = (actual, expected) {
+                    assert_eq!(actual.first(), Some(&start));
+                    assert_eq!(actual.last(), Some(&goal));
+                    assert_eq!(
+                        path_length(planner.map(), &actual),
+                        path_length(planner.map(), &expected)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_planner_small_change_is_cheap() {
+        let map = Map::from_str(&vec![" ".repeat(60); 60].join("\n")).unwrap();
+        let start = Point { x: 5, y: 5 };
+        let goal = Point { x: 25, y: 25 };
+        let mut planner = Planner::new(map, start, goal, &SearchBudget::default()).unwrap();
+        let initial = planner.expanded;
+
+        // Стена далеко от пути не требует перерасчёта
+        planner
+            .update_cells(&[(Point { x: 45, y: 45 }, true)], &SearchBudget::default())
+            .unwrap();
+        assert_eq!(planner.expanded, 0);
+
+        // Стена на пути чинится локально
+        let on_path = planner.path().unwrap()[10];
+        planner
+            .update_cells(&[(on_path, true)], &SearchBudget::default())
+            .unwrap();
+        assert_eq!(path_length(planner.map(), &planner.path().unwrap()), 40);
+        assert!(planner.expanded < initial);
+    }
+
+    #[test]
+    fn test_planner_budget() {
+        let map = Map::from_str(&vec![" ".repeat(60); 60].join("\n")).unwrap();
+        let start = Point { x: 5, y: 5 };
+        let goal = Point { x: 25, y: 25 };
+        let small = SearchBudget {
+            max_expanded: Some(10),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            Planner::new(map.clone(), start, goal, &small).err(),
+            Some(BudgetExceeded::Expanded)
+        );
+
+        // Стена на пути требует перерасчёта, который не укладывается в бюджет
+        let mut planner = Planner::new(map, start, goal, &SearchBudget::default()).unwrap();
+        let path = planner.path().unwrap();
+        let on_path = path[10];
+        assert_eq!(
+            planner.update_cells(&[(on_path, true)], &small),
+            Err(BudgetExceeded::Expanded)
+        );
+        // Прерванный перерасчёт откатан вместе с клеткой
+        assert!(planner.map().is_open(on_path));
+        assert_eq!(planner.path(), Some(path));
+
+        // После отката планировщик чинит путь так же, как без него
+        let mut rng = Lcg(34);
+        for _ in 0..40 {
+            // Половина изменений - стены на текущем пути
+            let point = match planner.path() {
+                Some(path) if rng.next(2) == 0 => path[rng.next(path.len())],
+                _ => Point {
+                    x: rng.next(60),
+                    y: rng.next(60),
+                },
+            };
+            if point == start || point == goal {
+                continue;
+            }
+            let wall = rng.next(3) > 0;
+            let budget = SearchBudget {
+                max_expanded: Some(1 + rng.next(6)),
+                ..SearchBudget::default()
+            };
+            let before = planner.map().clone();
+            if planner.update_cells(&[(point, wall)], &budget).is_err() {
+                assert_eq!(planner.map(), &before);
+            }
+            let expected = planner.map().search(start, goal, Algorithm::Bfs).path;
+            assert_eq!(
+                planner.path().map(|path| path.len()),
+                expected.map(|path| path.len())
+            );
+        }
+    }
+
+    #[test]
+    fn test_planner_queue_stays_compact() {
+        let map = Map::from_str(&vec![" ".repeat(120); 120].join("\n")).unwrap();
+        let start = Point { x: 5, y: 5 };
+        let goal = Point { x: 90, y: 70 };
+        let mut planner = Planner::new(map, start, goal, &SearchBudget::default()).unwrap();
+
+        // Каждое изменение оставляет в очереди устаревшие записи; без
+        // перестройки их число росло бы с каждым вызовом
+        for _ in 0..300 {
+            let path = planner.path().unwrap();
+            let point = path[path.len() / 2];
+            for wall in [true, false] {
+                planner
+                    .update_cells(&[(point, wall)], &SearchBudget::default())
+                    .unwrap();
+            }
+            assert!(planner.queue_len() < 1024, "{}", planner.queue_len());
+        }
+        let expected = planner.map().search(start, goal, Algorithm::Bfs).path;
+        assert_eq!(
+            planner.path().map(|path| path.len()),
+            expected.map(|path| path.len())
+        );
+    }
+
+    #[test]
+    fn test_smooth_path_open_area() {
+        let map = Map::from_str(&vec![" ".repeat(20); 16].join("\n")).unwrap();
+        let path = map
+            .search(Point { x: 1, y: 1 }, Point { x: 4, y: 7 }, Algorithm::Bfs)
+            .path
+            .unwrap();
+
+        assert_eq!(map.smooth_path(&path), vec![(1.5, 1.5), (4.5, 7.5)]);
+    }
+
+    #[test]
+    fn test_smooth_path_goes_around_walls() {
+        let map = Map::from_str("#######\n#     #\n# ### #\n#     #\n#######").unwrap();
+        let path = map
+            .search(Point { x: 1, y: 1 }, Point { x: 3, y: 5 }, Algorithm::Bfs)
+            .path
+            .unwrap();
+
+        let waypoints = map.smooth_path(&path);
+
+        assert_eq!(waypoints.len(), 3);
+        assert_eq!(waypoints.first(), Some(&(1.5, 1.5)));
+        assert_eq!(waypoints.last(), Some(&(3.5, 5.5)));
+    }
+
+    #[test]
+    fn test_smooth_path_across_seam() {
+        let map = Map::from_str("##########\n          \n##########").unwrap();
+        let path = map
+            .search(Point { x: 1, y: 1 }, Point { x: 1, y: 8 }, Algorithm::Bfs)
+            .path
+            .unwrap();
+
+        // Кратчайший путь идёт влево через шов
+        assert_eq!(map.smooth_path(&path), vec![(1.5, 1.5), (1.5, -1.5)]);
+    }
+
+    #[test]
+    fn test_smooth_path_does_not_cut_corners() {
+        let map = Map::from_str("####\n# ##\n#  #\n####").unwrap();
+        let path = map
+            .search(Point { x: 1, y: 1 }, Point { x: 2, y: 2 }, Algorithm::Bfs)
+            .path
+            .unwrap();
+
+        assert_eq!(
+            map.smooth_path(&path),
+            vec![(1.5, 1.5), (2.5, 1.5), (2.5, 2.5)]
+        );
+    }
+
+    // Проверяет, что пути агентов не пересекаются ни в клетках, ни на рёбрах
+    fn assert_conflict_free(map: &Map, agents: &[Agent], paths: &[Vec<Point>]) {
+        let makespan = paths[0].len();
+        for (agent, path) in agents.iter().zip(paths) {
+            assert_eq!(path.len(), makespan);
+            assert_eq!(path.first(), Some(&agent.start));
+            assert_eq!(path.last(), Some(&agent.goal));
+            for step in path.windows(2) {
+                assert!(step[0] == step[1] || map.get_neighbors(step[0]).contains(&step[1]));
+            }
+        }
+
+        for t in 0..makespan {
+            for a in 0..paths.len() {
+                for b in a + 1..paths.len() {
+                    assert_ne!(paths[a][t], paths[b][t], "vertex conflict at t={}", t);
+                    if t > 0 {
+                        assert!(
+                            paths[a][t] != paths[b][t - 1] || paths[b][t] != paths[a][t - 1],
+                            "swap conflict at t={}",
+                            t
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_agent_pass_in_corridor_with_bay() {
+        let map = Map::from_str("#######\n#     #\n#### ##\n#######").unwrap();
+        let agents = [
+            Agent {
+                start: Point { x: 1, y: 1 },
+                goal: Point { x: 1, y: 5 },
+            },
+            Agent {
+                start: Point { x: 1, y: 5 },
+                goal: Point { x: 1, y: 1 },
+            },
+        ];
+
+        let paths = multi_agent::plan(&map, &agents, &SearchBudget::default()).unwrap();
+
+        assert_conflict_free(&map, &agents, &paths);
+        // Второй агент пропускает первого, отступив в нишу
+        assert!(paths[1].contains(&Point { x: 2, y: 4 }));
+        assert_eq!(paths[0].len(), 8);
+    }
+
+    #[test]
+    fn test_multi_agent_random_maps() {
+        let mut rng = Lcg(11);
+        let mut planned = 0;
+        for _ in 0..100 {
+            let map = random_map(&mut rng, 8, 8, 15);
+            let mut agents: Vec<Agent> = Vec::new();
+            for _ in 0..4 {
+                let start = random_open_point(&mut rng, &map);
+                let goal = random_open_point(&mut rng, &map);
+                let (Some(start), Some(goal)) = (start, goal) else {
+                    continue;
+                };
+                if agents.iter().all(|a| a.start != start && a.goal != goal) {
+                    agents.push(Agent { start, goal });
+                }
+            }
+
+            if let Ok(paths) = multi_agent::plan(&map, &agents, &SearchBudget::default()) {
+                assert_conflict_free(&map, &agents, &paths);
+                planned += 1;
+            }
+        }
+        assert!(planned > 50);
+    }
+
+    #[test]
+    fn test_multi_agent_errors() {
+        let map = Map::from_str("#####\n#   #\n#####").unwrap();
+        let shared = [
+            Agent {
+                start: Point { x: 1, y: 1 },
+                goal: Point { x: 1, y: 3 },
+            },
+            Agent {
+                start: Point { x: 1, y: 2 },
+                goal: Point { x: 1, y: 3 },
+            },
+        ];
+        assert_eq!(
+            multi_agent::plan(&map, &shared, &SearchBudget::default()),
+            Err(PlanError::SharedEndpoint)
+        );
+
+        // В тупиковом коридоре агенты не могут разминуться
+        let blocked = [
+            Agent {
+                start: Point { x: 1, y: 1 },
+                goal: Point { x: 1, y: 3 },
+            },
+            Agent {
+                start: Point { x: 1, y: 3 },
+                goal: Point { x: 1, y: 1 },
+            },
+        ];
+        assert_eq!(
+            multi_agent::plan(&map, &blocked, &SearchBudget::default()),
+            Err(PlanError::NoPath(1))
+        );
+
+        // Цель за стеной отсекается до поиска в пространстве-времени
+        let walled = Map::from_str("#####\n# # #\n#####").unwrap();
+        let unreachable = [Agent {
+            start: Point { x: 1, y: 1 },
+            goal: Point { x: 1, y: 3 },
+        }];
+        assert_eq!(
+            multi_agent::plan(&walled, &unreachable, &SearchBudget::default()),
+            Err(PlanError::Unreachable(0))
+        );
+
+        let crowd: Vec<Agent> = (0..=multi_agent::MAX_AGENTS)
+            .map(|y| Agent {
+                start: Point { x: 0, y },
+                goal: Point { x: 1, y },
+            })
+            .collect();
+        let open = Map::with_size(2, crowd.len());
+        assert_eq!(
+            multi_agent::plan(&open, &crowd, &SearchBudget::default()),
+            Err(PlanError::TooManyAgents)
+        );
+    }
+
+    #[test]
+    fn test_multi_agent_budget() {
+        let map = Map::from_str("#######\n#     #\n#### ##\n#######").unwrap();
+        let agents = [
+            Agent {
+                start: Point { x: 1, y: 1 },
+                goal: Point { x: 1, y: 5 },
+            },
+            Agent {
+                start: Point { x: 1, y: 5 },
+                goal: Point { x: 1, y: 1 },
+            },
+        ];
+        let small = SearchBudget {
+            max_expanded: Some(5),
+            ..SearchBudget::default()
+        };
+
+        assert_eq!(
+            multi_agent::plan(&map, &agents, &small),
+            Err(PlanError::BudgetExceeded(BudgetExceeded::Expanded))
+        );
+    }
+
+    #[test]
+    fn test_multi_agent_frames() {
+        let map = Map::from_str("#####\n#   #\n#####").unwrap();
+        let agents = [Agent {
+            start: Point { x: 1, y: 1 },
+            goal: Point { x: 1, y: 3 },
+        }];
+
+        let paths = multi_agent::plan(&map, &agents, &SearchBudget::default()).unwrap();
+        let frames = multi_agent::render_frames(&map, &paths);
+
+        assert_eq!(
+            frames,
+            vec![
+                "#####\n#0  #\n#####",
+                "#####\n# 0 #\n#####",
+                "#####\n#  0#\n#####",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intercept_stationary_target() {
+        let map = Map::from_str("#####\n#   #\n# # #\n#   #\n#####").unwrap();
+        let interception = map
+            .intercept(
+                Point { x: 1, y: 1 },
+                Point { x: 3, y: 3 },
+                &TargetPolicy::Route(Vec::new()),
+            )
+            .unwrap();
+
+        assert_eq!(interception.time, 4);
+        assert_eq!(path_length(&map, &interception.path), 4);
+        assert_eq!(interception.target_path, vec![Point { x: 3, y: 3 }; 5]);
+    }
+
+    #[test]
+    fn test_intercept_target_running_across_seam() {
+        let map = Map::from_str("##########\n          \n##########").unwrap();
+        let route = vec![
+            Point { x: 1, y: 3 },
+            Point { x: 1, y: 4 },
+            Point { x: 1, y: 5 },
+        ];
+        let interception = map
+            .intercept(
+                Point { x: 1, y: 0 },
+                Point { x: 1, y: 2 },
+                &TargetPolicy::Route(route),
+            )
+            .unwrap();
+
+        // Цель убегает с той же скоростью, поймать её можно только
+        // после остановки
+        assert_eq!(interception.time, 5);
+        assert_eq!(interception.path.len(), 6);
+        assert_eq!(interception.path[5], Point { x: 1, y: 5 });
+        assert_eq!(interception.target_path[5], Point { x: 1, y: 5 });
+    }
+
+    #[test]
+    fn test_intercept_target_coming_closer() {
+        let map = Map::from_str("##########\n          \n##########").unwrap();
+        let route = vec![
+            Point { x: 1, y: 4 },
+            Point { x: 1, y: 3 },
+            Point { x: 1, y: 2 },
+        ];
+        let interception = map
+            .intercept(
+                Point { x: 1, y: 0 },
+                Point { x: 1, y: 5 },
+                &TargetPolicy::Route(route),
+            )
+            .unwrap();
+
+        assert_eq!(interception.time, 3);
+        assert_eq!(interception.path.last(), Some(&Point { x: 1, y: 2 }));
+        for t in 0..=interception.time {
+            assert!(interception.path[t] != interception.target_path[t] || t == 3);
+        }
+    }
+
+    #[test]
+    fn test_intercept_fleeing_target() {
+        let map = Map::from_str("##########\n          \n##########").unwrap();
+        let interception = map
+            .intercept(
+                Point { x: 1, y: 0 },
+                Point { x: 1, y: 1 },
+                &TargetPolicy::Flee,
+            )
+            .unwrap();
+
+        // Дальше всего от преследователя - противоположная точка кольца
+        assert_eq!(interception.target_path.last(), Some(&Point { x: 1, y: 5 }));
+        assert_eq!(interception.time, 5);
+    }
+
+    #[test]
+    fn test_pursue_budget() {
+        let map = Map::from_str("##########\n          \n##########").unwrap();
+        let (pursuer, target) = (Point { x: 1, y: 0 }, Point { x: 1, y: 1 });
+
+        let pursuit = map
+            .pursue(
+                pursuer,
+                target,
+                &TargetPolicy::Flee,
+                &SearchBudget::default(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(pursuit.trajectory.last(), Some(&Point { x: 1, y: 5 }));
+        assert_eq!(pursuit.interception.map(|i| i.time), Some(5));
+
+        // Бюджет общий: каждый обход по отдельности в него укладывается
+        let budget = SearchBudget {
+            max_expanded: Some(15),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            map.pursue(pursuer, target, &TargetPolicy::Flee, &budget),
+            Err(BudgetExceeded::Expanded)
+        );
+    }
+
+    #[test]
+    fn test_intercept_invalid_and_unreachable() {
+        let map = Map::from_str("#####\n# # #\n#####").unwrap();
+        let pursuer = Point { x: 1, y: 1 };
+        let target = Point { x: 1, y: 3 };
+
+        let jump = TargetPolicy::Route(vec![Point { x: 1, y: 1 }]);
+        assert_eq!(map.target_trajectory(pursuer, target, &jump), None);
+        assert_eq!(
+            map.intercept(pursuer, target, &TargetPolicy::Route(Vec::new())),
+            None
+        );
+        // Цель, до которой не добраться, стоит на месте
+        assert_eq!(
+            map.target_trajectory(pursuer, target, &TargetPolicy::Flee),
+            Some(vec![target])
+        );
+    }
+
+    #[test]
+    fn test_hex_map_parse_and_display() {
+        let input = "!hex\n#  #\n    \n# # \n  # ";
+        let map = Map::from_str(input).unwrap();
+
+        assert_eq!(map.kind, GridKind::Hex);
+        assert_eq!((map.rows, map.cols), (4, 4));
+        assert_eq!(map.to_string(), input);
+        // На шестиугольном торе число строк должно быть чётным
+        assert_eq!(Map::from_str("!hex\n  \n  \n  "), Err(ParsePointError));
+        assert_eq!(Map::from_str("!hex"), Err(ParsePointError));
+    }
+
+    #[test]
+    fn test_hex_neighbors() {
+        let map = Map::from_str(&format!("!hex\n{}", ["     "; 4].join("\n"))).unwrap();
+        let sorted = |point| {
+            let mut neighbors = map.get_neighbors(point);
+            neighbors.sort_by_key(|p| (p.x, p.y));
+            neighbors
+        };
+
+        // Чётная строка: соседи сверху и снизу сдвинуты влево
+        assert_eq!(
+            sorted(Point { x: 2, y: 2 }),
+            vec![
+                Point { x: 1, y: 1 },
+                Point { x: 1, y: 2 },
+                Point { x: 2, y: 1 },
+                Point { x: 2, y: 3 },
+                Point { x: 3, y: 1 },
+                Point { x: 3, y: 2 },
+            ]
+        );
+        // Нечётная строка у правого края: соседи через оба шва
+        assert_eq!(
+            sorted(Point { x: 3, y: 4 }),
+            vec![
+                Point { x: 0, y: 0 },
+                Point { x: 0, y: 4 },
+                Point { x: 2, y: 0 },
+                Point { x: 2, y: 4 },
+                Point { x: 3, y: 0 },
+                Point { x: 3, y: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hex_distance_matches_bfs() {
+        for (rows, cols) in [(4, 5), (6, 3), (10, 2), (2, 9), (8, 8)] {
+            let mut map = Map::from_str(&vec![" ".repeat(cols); rows].join("\n")).unwrap();
+            map.kind = GridKind::Hex;
+
+            for x in 0..rows {
+                for y in 0..cols {
+                    let from = Point { x, y };
+                    let distances = map.distances_from(from);
+                    for tx in 0..rows {
+                        for ty in 0..cols {
+                            let to = Point { x: tx, y: ty };
+                            assert_eq!(Some(map.hex_distance(from, to)), distances.get(to));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hex_algorithms_agree() {
+        let mut rng = Lcg(37);
+
+        for _ in 0..300 {
+            let rows = 2 * (1 + rng.next(7));
+            let cols = 1 + rng.next(14);
+            let wall_percent = 15 + rng.next(30);
+            let mut map = random_map(&mut rng, rows, cols, wall_percent);
+            map.kind = GridKind::Hex;
+            let (Some(start), Some(finish)) = (
+                random_open_point(&mut rng, &map),
+                random_open_point(&mut rng, &map),
+            ) else {
+                continue;
+            };
+
+            let reference = map.distances_from(start).get(finish);
+            for algorithm in [
+                Algorithm::Bfs,
+                Algorithm::BidirectionalBfs,
+                Algorithm::AStar,
+                Algorithm::Jps,
+                Algorithm::Hpa,
+            ] {
+                let path = map.search(start, finish, algorithm).path;
+                assert_eq!(path.map(|path| path_length(&map, &path)), reference);
+            }
+
+            let planner =
+                Planner::new(map.clone(), start, finish, &SearchBudget::default()).unwrap();
+            assert_eq!(
+                planner.path().map(|path| path_length(&map, &path)),
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_hex_diameter_and_components() {
+        let mut rng = Lcg(41);
+
+        for _ in 0..50 {
+            let mut map = random_map(&mut rng, 6, 7, 30);
+            map.kind = GridKind::Hex;
+
+            if let Some(diameter) = map.diameter() {
+                assert_eq!(diameter.length, brute_force_diameter(&map));
+            }
+            let components = map.connected_components();
+            for x in 0..map.rows {
+                for y in 0..map.cols {
+                    let point = Point { x, y };
+                    for neighbor in map.distinct_neighbors(point) {
+                        assert_eq!(
+                            components.component_of(point),
+                            components.component_of(neighbor)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_layered_map_parse_and_display() {
+        let input = "#####\n#  H#\n#####\n\n#####\n#  H#\n#####";
+        let map = LayeredMap::from_str(input).unwrap();
+
+        assert_eq!((map.depth(), map.rows, map.cols), (2, 3, 5));
+        assert_eq!(map.wrap, Wrap::default());
+        assert_eq!(map.to_string(), input);
+
+        let input = format!("!wrap z\n{}", input);
+        let map = LayeredMap::from_str(&input).unwrap();
+        assert_eq!(
+            map.wrap,
+            Wrap {
+                x: false,
+                y: false,
+                z: true
+            }
+        );
+        assert_eq!(map.to_string(), input);
+
+        assert_eq!(
+            LayeredMap::from_str("###\n# #\n\n####\n#  #"),
+            Err(ParsePointError)
+        );
+        assert_eq!(LayeredMap::from_str("!wrap w\n# #"), Err(ParsePointError));
+    }
+
+    #[test]
+    fn test_layered_path_uses_stairs() {
+        let mut map = LayeredMap::from_str("#####\n#  H#\n#####\n\n#####\n#  H#\n#####").unwrap();
+        let start = Point3 { x: 1, y: 1, z: 0 };
+        let finish = Point3 { x: 1, y: 1, z: 1 };
+
+        let path = map.find_path(start, finish).unwrap();
+
+        assert_eq!(path.len(), 6);
+        assert!(path.contains(&Point3 { x: 1, y: 3, z: 0 }));
+        assert!(path.contains(&Point3 { x: 1, y: 3, z: 1 }));
+
+        map.mark_path(&path);
+        assert_eq!(
+            map.to_string(),
+            "#####\n#i.H#\n#####\n\n#####\n#O.H#\n#####"
+        );
+    }
+
+    #[test]
+    fn test_layered_wrap_per_axis() {
+        let corridor = "     ";
+        let start = Point3 { x: 0, y: 0, z: 0 };
+        let finish = Point3 { x: 0, y: 4, z: 0 };
+
+        let wrapped = LayeredMap::from_str(corridor).unwrap();
+        assert_eq!(wrapped.find_path(start, finish).unwrap().len(), 2);
+        let bounded = LayeredMap::from_str(&format!("!wrap\n{}", corridor)).unwrap();
+        assert_eq!(bounded.find_path(start, finish).unwrap().len(), 5);
+
+        // Лестница в каждом из трёх слоёв; сверху можно попасть вниз,
+        // только если ось z замкнута
+        let tower = "H\n\nH\n\nH";
+        let top = Point3 { x: 0, y: 0, z: 2 };
+        let bottom = Point3 { x: 0, y: 0, z: 0 };
+        let open = LayeredMap::from_str(tower).unwrap();
+        assert_eq!(open.find_path(bottom, top).unwrap().len(), 3);
+        let ring = LayeredMap::from_str(&format!("!wrap z\n{}", tower)).unwrap();
+        assert_eq!(ring.find_path(bottom, top).unwrap().len(), 2);
+
+        // Без лестницы на соседнем слое подняться нельзя
+        let blocked = LayeredMap::from_str("H\n\n ").unwrap();
+        assert_eq!(blocked.find_path(bottom, Point3 { x: 0, y: 0, z: 1 }), None);
+    }
+
+    // Ориентированный граф со списками смежности и координатами вершин
+    // на прямой для эвристики
+    struct TestGraph {
+        edges: Vec<Vec<(usize, u32)>>,
+        positions: Vec<u32>,
+    }
+
+    impl TestGraph {
+        fn new(nodes: usize) -> Self {
+            TestGraph {
+                edges: vec![Vec::new(); nodes],
+                positions: vec![0; nodes],
+            }
+        }
+
+        fn connect(&mut self, a: usize, b: usize, cost: u32) {
+            self.edges[a].push((b, cost));
+            self.edges[b].push((a, cost));
+        }
+    }
+
+    impl Graph for TestGraph {
+        type Node = usize;
+
+        fn node_count(&self) -> usize {
+            self.edges.len()
+        }
+
+        fn index(&self, node: usize) -> usize {
+            node
+        }
+
+        fn node(&self, index: usize) -> usize {
+            index
+        }
+
+        fn neighbors(&self, node: usize) -> Vec<(usize, u32)> {
+            self.edges[node].clone()
+        }
+
+        fn heuristic(&self, from: usize, to: usize) -> u32 {
+            self.positions[from].abs_diff(self.positions[to])
+        }
+    }
+
+    #[test]
+    fn test_graph_weighted_search() {
+        // 0 -5- 1 -5- 3 и обход 0 -1- 2 -1- 4 -1- 3
+        let mut test_graph = TestGraph::new(5);
+        test_graph.connect(0, 1, 5);
+        test_graph.connect(1, 3, 5);
+        test_graph.connect(0, 2, 1);
+        test_graph.connect(2, 4, 1);
+        test_graph.connect(4, 3, 1);
+        test_graph.positions = vec![0, 2, 1, 3, 2];
+
+        let tree = graph::dijkstra(&test_graph, 0, None);
+        assert_eq!(
+            tree.distances,
+            vec![Some(0), Some(5), Some(1), Some(3), Some(2)]
+        );
+        assert_eq!(tree.path_to(&test_graph, 3), Some(vec![0, 2, 4, 3]));
+
+        let (path, _) = graph::astar(&test_graph, 0, 3);
+        assert_eq!(path, Some(vec![0, 2, 4, 3]));
+
+        // BFS считает ходы, а не цену
+        let tree = graph::bfs(&test_graph, 0, None);
+        assert_eq!(tree.path_to(&test_graph, 3), Some(vec![0, 1, 3]));
+        assert_eq!(
+            graph::bidirectional_bfs(&test_graph, 0, 3)
+                .0
+                .map(|p| p.len()),
+            Some(3)
+        );
+
+        // Вершины на предельной глубине достигаются, но не раскрываются
+        let tree = graph::bfs_bounded_observed(&test_graph, 0, None, Some(1), &mut NoObserver);
+        assert_eq!(tree.distances, vec![Some(0), Some(1), Some(1), None, None]);
+        assert_eq!(tree.expanded, 3);
+    }
+
+    #[test]
+    fn test_graph_random_agreement() {
+        let mut rng = Lcg(39);
+
+        for _ in 0..200 {
+            let nodes = 2 + rng.next(30);
+            let mut test_graph = TestGraph::new(nodes);
+            for _ in 0..rng.next(3 * nodes) {
+                let (a, b) = (rng.next(nodes), rng.next(nodes));
+                let cost = 1 + rng.next(9) as u32;
+                test_graph.connect(a, b, cost);
+            }
+            let (start, finish) = (rng.next(nodes), rng.next(nodes));
+
+            let costs = graph::dijkstra(&test_graph, start, None).distances;
+            let (path, _) = graph::astar(&test_graph, start, finish);
+            let cost = path.map(|path| {
+                path.windows(2)
+                    .map(|pair| {
+                        test_graph.edges[pair[0]]
+                            .iter()
+                            .filter(|&&(node, _)| node == pair[1])
+                            .map(|&(_, cost)| cost)
+                            .min()
+                            .unwrap()
+                    })
+                    .sum::<u32>()
+            });
+            assert_eq!(cost, costs[finish]);
+
+            let moves = graph::bfs(&test_graph, start, None).distances[finish];
+            let (path, _) = graph::bidirectional_bfs(&test_graph, start, finish);
+            assert_eq!(path.map(|path| path.len() as u32 - 1), moves);
+        }
+    }
+
+    #[test]
+    fn test_layered_map_as_graph() {
+        let map = LayeredMap::from_str("#####\n#  H#\n#####\n\n#####\n#  H#\n#####").unwrap();
+        let start = Point3 { x: 1, y: 1, z: 0 };
+        let finish = Point3 { x: 1, y: 1, z: 1 };
+
+        for index in 0..map.node_count() {
+            assert_eq!(map.index(map.node(index)), index);
+        }
+        let (path, _) = graph::astar(&map, start, finish);
+        assert_eq!(path, map.find_path(start, finish));
+    }
+
+    #[test]
+    fn test_map_cells_roundtrip() {
+        // 9x9 = 81 клетка - стены занимают два 64-битных слова
+        let mut map = Map::with_size(9, 9);
+        let cells = [
+            Cell::Wall,
+            Cell::Empty,
+            Cell::Start,
+            Cell::End,
+            Cell::Path,
+            Cell::Stairs,
+        ];
+
+        for x in 0..9 {
+            for y in 0..9 {
+                map.set_cell(Point { x, y }, cells[(x * 9 + y) % cells.len()]);
+            }
+        }
+        for x in 0..9 {
+            for y in 0..9 {
+                let expected = cells[(x * 9 + y) % cells.len()];
+                assert_eq!(map.cell(Point { x, y }), expected);
+                assert_eq!(map.is_open(Point { x, y }), expected != Cell::Wall);
+            }
+        }
+
+        // Стена поверх отметки убирает её, и наоборот
+        map.set_cell(Point { x: 0, y: 2 }, Cell::Wall);
+        assert_eq!(map.cell(Point { x: 0, y: 2 }), Cell::Wall);
+        map.set_cell(Point { x: 0, y: 2 }, Cell::Empty);
+        assert_eq!(map.cell(Point { x: 0, y: 2 }), Cell::Empty);
+        assert_eq!(map.find_cell(Cell::Start), Some(Point { x: 0, y: 8 }));
+
+        let grid: Vec<Vec<Cell>> = (0..9).map(|x| map.row_cells(x).collect()).collect();
+        assert_eq!(Map::from_grid(&grid), map);
+    }
+
+    #[test]
+    fn test_search_trace_events() {
+        let map =
+            Map::from_str("#######\n#   # #\n# # # #\n# #   #\n# ### #\n#     #\n#######").unwrap();
+        let start = Point { x: 1, y: 1 };
+        let finish = Point { x: 3, y: 4 };
+
+        for algorithm in [
+            Algorithm::Bfs,
+            Algorithm::BidirectionalBfs,
+            Algorithm::Dijkstra,
+            Algorithm::AStar,
+        ] {
+            let mut recorder = TraceRecorder::default();
+            let result = map.search_observed(start, finish, algorithm, &mut recorder);
+
+            // Поиск с наблюдателем находит тот же путь
+            assert_eq!(result, map.search(start, finish, algorithm));
+            assert_eq!(recorder.events[0], TraceEvent::Push(start));
+
+            let pops: Vec<Point> = recorder
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    TraceEvent::Pop(point) => Some(*point),
+                    TraceEvent::Push(_) => None,
+                })
+                .collect();
+            assert_eq!(pops.len(), result.expanded);
+            // Раскрывается только то, что раньше попало во фронт
+            for (position, event) in recorder.events.iter().enumerate() {
+                if let TraceEvent::Pop(point) = event {
+                    assert!(recorder.events[..position].contains(&TraceEvent::Push(*point)));
+                }
+            }
+        }
+
+        // Запись сверх предела отбрасывается и останавливает поиск
+        let mut recorder = TraceRecorder::with_limit(5);
+        let result = map.search_observed(start, finish, Algorithm::Bfs, &mut recorder);
+        assert!(recorder.truncated);
+        assert_eq!(recorder.events.len(), 5);
+        assert_eq!(result.path, None);
+    }
+
+    // Останавливает поиск после заданного числа раскрытий
+    struct StopAfter(usize);
+
+    impl SearchObserver<Point> for StopAfter {
+        fn on_pop(&mut self, _point: Point) {
+            self.0 = self.0.saturating_sub(1);
+        }
+
+        fn keep_going(&self) -> bool {
+            self.0 > 0
+        }
+    }
+
+    #[test]
+    fn test_search_stops_when_observer_asks() {
+        let map = Map::with_size(20, 20);
+        let start = Point { x: 0, y: 0 };
+        let finish = Point { x: 10, y: 10 };
+
+        for algorithm in [
+            Algorithm::Bfs,
+            Algorithm::BidirectionalBfs,
+            Algorithm::Dijkstra,
+            Algorithm::AStar,
+        ] {
+            let result = map.search_observed(start, finish, algorithm, &mut StopAfter(5));
+            assert_eq!(result.expanded, 5);
+            assert_eq!(result.path, None);
+        }
+    }
+
+    #[test]
+    fn test_generate_maze() {
+        let map = Map::generate_maze(12, 16, 7, &mut NoObserver);
+        assert_eq!((map.rows, map.cols), (12, 16));
+        assert!(metrics::compute(&map, None).is_perfect);
+        // Комнаты - все клетки с чётными координатами
+        assert_eq!(metrics::compute(&map, None).open_cells, 6 * 8 * 2 - 1);
+
+        assert_eq!(Map::generate_maze(12, 16, 7, &mut NoObserver), map);
+        assert_ne!(Map::generate_maze(12, 16, 8, &mut NoObserver), map);
+
+        // Прерванная генерация успевает вырубить только часть комнат
+        let budget = SearchBudget {
+            max_expanded: Some(5),
+            ..SearchBudget::default()
+        };
+        let mut unobserved = NoObserver;
+        let mut observer = Budgeted::new(&budget, &mut unobserved);
+        let partial = Map::generate_maze(12, 16, 7, &mut observer);
+        assert_eq!(observer.finish(()), Err(BudgetExceeded::Expanded));
+        assert_eq!(metrics::compute(&partial, None).open_cells, 9);
+    }
+
+    // Ждёт, пока задание не перейдёт в нужное состояние
+    async fn wait_for_status(queue: &JobQueue, id: uuid::Uuid, status: JobStatus) {
+        for _ in 0..200 {
+            if queue.get(id).map(|job| job.status) == Some(status) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        panic!("job never became {:?}", status);
+    }
+
+    #[tokio::test]
+    async fn test_job_queue_limit_and_ttl() {
+        let queue = JobQueue::start(JobConfig {
+            workers: 1,
+            queue_limit: 1,
+            ttl: std::time::Duration::ZERO,
+            ..JobConfig::default()
+        });
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+
+        let running = queue
+            .submit(
+                "test",
+                None,
+                None,
+                Box::new(move |_| {
+                    wait.recv().ok();
+                    Ok(serde_json::Value::Null)
+                }),
+            )
+            .unwrap();
+        wait_for_status(&queue, running, JobStatus::Running).await;
+
+        // Единственный работник занят, а в очереди одно место
+        let queued = queue
+            .submit(
+                "test",
+                None,
+                None,
+                Box::new(|_| Ok(serde_json::Value::Null)),
+            )
+            .unwrap();
+        let rejected = queue.submit(
+            "test",
+            None,
+            None,
+            Box::new(|_| Ok(serde_json::Value::Null)),
+        );
+        assert_eq!(rejected.err(), Some(SubmitError::QueueFull));
+        assert_eq!(queue.get(queued).unwrap().status, JobStatus::Queued);
+
+        // С нулевым сроком хранения завершённые задания сразу забываются
+        release.send(()).unwrap();
+        for _ in 0..200 {
+            if queue.get(running).is_none() && queue.get(queued).is_none() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        panic!("finished jobs were not dropped");
+    }
+
+    #[tokio::test]
+    async fn test_job_cancel_keeps_record() {
+        let queue = JobQueue::start(JobConfig {
+            workers: 1,
+            ..JobConfig::default()
+        });
+        let (release, wait) = std::sync::mpsc::channel::<()>();
+
+        let running = queue
+            .submit(
+                "test",
+                None,
+                None,
+                Box::new(move |_| {
+                    wait.recv().ok();
+                    Ok(serde_json::Value::Null)
+                }),
+            )
+            .unwrap();
+        wait_for_status(&queue, running, JobStatus::Running).await;
+        let queued = queue
+            .submit(
+                "test",
+                None,
+                None,
+                Box::new(|_| Ok(serde_json::Value::Null)),
+            )
+            .unwrap();
+
+        assert!(queue.cancel(queued));
+        assert!(queue.cancel(running));
+        assert!(!queue.cancel(uuid::Uuid::new_v4()));
+
+        // Итог отменённого задания отбрасывается, а запись остаётся
+        release.send(()).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(queue.get(running).unwrap().status, JobStatus::Cancelled);
+        assert_eq!(queue.get(running).unwrap().result, None);
+        assert_eq!(queue.get(queued).unwrap().status, JobStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        let vars = std::collections::HashMap::from([
+            ("JOB_WORKERS", "3"),
+            ("JOB_TIME_LIMIT_MS", "1500"),
+            ("MAP_MAX_CELLS", "100"),
+            ("BODY_LIMIT_BYTES", "2048"),
+            ("SOLVE_CACHE_BYTES", "0"),
+        ]);
+        let config = crate::config_from(|name| vars.get(name).map(|value| value.to_string()));
+        let config = config.unwrap();
+        assert_eq!(config.map_limits.max_cells, 100);
+        assert_eq!(config.map_limits.max_rows, MapLimits::default().max_rows);
+        assert_eq!(config.body_limit.0, 2048);
+        assert_eq!(config.solve_cache_capacity.0, 0);
+        assert_eq!(config.jobs.workers, 3);
+        assert_eq!(
+            config.jobs.time_limit,
+            std::time::Duration::from_millis(1500)
+        );
+        assert_eq!(config.jobs.queue_limit, JobConfig::default().queue_limit);
+
+        let error = crate::config_from(|name| (name == "JOB_MAX_STEPS").then(|| "-1".to_string()));
+        assert_eq!(
+            error.err().unwrap(),
+            "JOB_MAX_STEPS must be a non-negative integer, got '-1'"
+        );
+    }
+
+    #[test]
+    fn test_search_budget() {
+        let map = Map::with_size(30, 30);
+        let start = Point { x: 0, y: 0 };
+        let finish = Point { x: 15, y: 15 };
+        let algorithms = [
+            Algorithm::Bfs,
+            Algorithm::BidirectionalBfs,
+            Algorithm::Dijkstra,
+            Algorithm::AStar,
+            Algorithm::Jps,
+            Algorithm::Hpa,
+        ];
+
+        for algorithm in algorithms {
+            let search = |budget: SearchBudget| {
+                map.search_budgeted(start, finish, algorithm, &budget, &mut NoObserver)
+            };
+            let unlimited = map.search(start, finish, algorithm);
+
+            assert_eq!(search(SearchBudget::default()), Ok(unlimited.clone()));
+            // Финиш, раскрытый на последнем разрешённом шаге, - успех
+            let exact = SearchBudget {
+                max_expanded: Some(unlimited.expanded),
+                ..SearchBudget::default()
+            };
+            assert_eq!(search(exact), Ok(unlimited.clone()));
+
+            let small = SearchBudget {
+                max_expanded: Some(2),
+                ..SearchBudget::default()
+            };
+            assert_eq!(search(small), Err(BudgetExceeded::Expanded));
+
+            let late = SearchBudget {
+                deadline: Some(std::time::Instant::now()),
+                ..SearchBudget::default()
+            };
+            assert_eq!(search(late), Err(BudgetExceeded::Deadline));
+
+            let token = CancelToken::default();
+            token.cancel();
+            let cancelled = SearchBudget {
+                cancel: Some(token),
+                ..SearchBudget::default()
+            };
+            assert_eq!(search(cancelled), Err(BudgetExceeded::Cancelled));
+        }
+    }
+
+    #[test]
+    fn test_layered_budget() {
+        let map = LayeredMap::from_str("#####\n#  H#\n#####\n\n#####\n#  H#\n#####").unwrap();
+        let start = Point3 { x: 1, y: 1, z: 0 };
+        let finish = Point3 { x: 1, y: 1, z: 1 };
+
+        assert_eq!(
+            map.find_path_budgeted(start, finish, &SearchBudget::default()),
+            Ok(map.find_path(start, finish))
+        );
+        let budget = SearchBudget {
+            max_expanded: Some(2),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            map.find_path_budgeted(start, finish, &budget),
+            Err(BudgetExceeded::Expanded)
+        );
+    }
+
+    #[test]
+    fn test_parse_with_limits() {
+        let input = "#####\n#   #\n#   #\n#####";
+        let limits = |max_rows, max_cols, max_cells| MapLimits {
+            max_rows,
+            max_cols,
+            max_cells,
+        };
+
+        assert_eq!(
+            Map::parse_with_limits(input, &limits(4, 5, 20)),
+            Ok(Map::from_str(input).unwrap())
+        );
+        assert_eq!(
+            Map::parse_with_limits(input, &limits(3, 5, 20)),
+            Err(ParseMapError::TooManyRows(3))
+        );
+        assert_eq!(
+            Map::parse_with_limits(input, &limits(4, 4, 20)),
+            Err(ParseMapError::TooManyCols(4))
+        );
+        assert_eq!(
+            Map::parse_with_limits(input, &limits(4, 5, 19)),
+            Err(ParseMapError::TooManyCells(19))
+        );
+        // Длина первой строки проверяется до того, как прочитаны остальные
+        assert_eq!(
+            Map::parse_with_limits("######\n#?", &limits(4, 5, 20)),
+            Err(ParseMapError::TooManyCols(5))
+        );
+        assert_eq!(
+            Map::parse_with_limits("#?#", &limits(4, 5, 20)),
+            Err(ParseMapError::Format)
+        );
+
+        // У многоуровневой карты предел клеток общий для всех слоёв
+        let layered = "###\n# #\n###\n\n###\n# #\n###";
+        assert!(LayeredMap::parse_with_limits(layered, &limits(3, 3, 18)).is_ok());
+        assert_eq!(
+            LayeredMap::parse_with_limits(layered, &limits(3, 3, 17)),
+            Err(ParseMapError::TooManyCells(17))
+        );
+    }
+
+    #[test]
+    fn test_lru_cache() {
+        let mut cache = LruCache::new(20);
+        cache.insert("a", 1, 10);
+        cache.insert("b", 2, 10);
+        assert_eq!(cache.get(&"a"), Some(&1));
+
+        // Вытесняется давно не использованная "b", а не первая вставленная "a"
+        cache.insert("c", 3, 10);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(&1));
+        assert_eq!(cache.get(&"c"), Some(&3));
+
+        cache.insert("c", 4, 5);
+        assert_eq!(cache.get(&"c"), Some(&4));
+        cache.retain(|key| *key != "a");
+        assert_eq!(cache.get(&"a"), None);
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 4,
+                misses: 2,
+                entries: 1,
+                bytes: 5,
+                capacity: 20,
+            }
+        );
+
+        // Большая запись вытесняет несколько мелких, а не влезающая
+        // целиком не хранится вовсе
+        cache.insert("d", 5, 10);
+        cache.insert("e", 6, 15);
+        assert_eq!(cache.get(&"c"), None);
+        assert_eq!(cache.get(&"d"), None);
+        assert_eq!(cache.get(&"e"), Some(&6));
+        cache.insert("f", 7, 21);
+        assert_eq!(cache.get(&"f"), None);
+        assert_eq!(cache.stats().bytes, 15);
+    }
+
+    #[test]
+    fn test_solve_batch_settles_on_last_expansion() {
+        let map = Map::from_str("#######\n#     #\n#######").unwrap();
+        let (a, b) = (Point { x: 1, y: 1 }, Point { x: 1, y: 5 });
+        let budget = |max_expanded| SearchBudget {
+            max_expanded: Some(max_expanded),
+            ..SearchBudget::default()
+        };
+
+        // Финиш раскрывается пятым, ровно на пределе бюджета
+        let routes = map
+            .solve_batch(
+                &[(a, b)],
+                Algorithm::Bfs,
+                Connectivity::Four,
+                &budget(5),
+                false,
+            )
+            .unwrap();
+        assert_eq!(routes[0].as_ref().map(|route| route.cost), Some(4));
+        assert_eq!(
+            map.solve_batch(
+                &[(a, b)],
+                Algorithm::Bfs,
+                Connectivity::Four,
+                &budget(4),
+                false
+            ),
+            Err(BudgetExceeded::Expanded)
+        );
+    }
+
+    #[test]
+    fn test_solve_batch() {
+        let map =
+            Map::from_str("#######\n#     #\n# ### #\n#   # #\n### # #\n#     #\n#######").unwrap();
+        let a = Point { x: 1, y: 1 };
+        let b = Point { x: 3, y: 1 };
+        let c = Point { x: 5, y: 5 };
+        let wall = Point { x: 0, y: 0 };
+        // Повторяющиеся старты, старт равный финишу и недостижимая цель
+        let pairs = [(a, c), (b, c), (a, b), (a, a), (c, wall), (a, c)];
+
+        for connectivity in [Connectivity::Four, Connectivity::Eight] {
+            for algorithm in [Algorithm::Bfs, Algorithm::Dijkstra] {
+                let serial = map
+                    .solve_batch(
+                        &pairs,
+                        algorithm,
+                        connectivity,
+                        &SearchBudget::default(),
+                        false,
+                    )
+                    .unwrap();
+                let parallel = map
+                    .solve_batch(
+                        &pairs,
+                        algorithm,
+                        connectivity,
+                        &SearchBudget::default(),
+                        true,
+                    )
+                    .unwrap();
+                assert_eq!(serial, parallel);
+
+                let graph = map.graph(connectivity);
+                for (&(start, finish), route) in pairs.iter().zip(&serial) {
+                    let tree = match algorithm {
+                        Algorithm::Dijkstra => graph::dijkstra(&graph, start, Some(finish)),
+                        _ => graph::bfs(&graph, start, Some(finish)),
+                    };
+                    let expected = tree.distances[graph.index(finish)];
+                    assert_eq!(route.as_ref().map(|route| route.cost), expected);
+                    if let Some(route) = route {
+                        assert_eq!(route.path.first(), Some(&start));
+                        assert_eq!(route.path.last(), Some(&finish));
+                    }
+                }
+                assert!(serial[4].is_none());
+            }
+        }
+
+        let small = SearchBudget {
+            max_expanded: Some(1),
+            ..SearchBudget::default()
+        };
+        assert_eq!(
+            map.solve_batch(&pairs, Algorithm::Bfs, Connectivity::Four, &small, true),
+            Err(BudgetExceeded::Expanded)
+        );
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        // Правая комната отрезана, через край тора проход есть
+        let map = Map::from_str("#####\n#   #\n  ## \n# # #\n#####").unwrap();
+        let a = Point { x: 1, y: 1 };
+        let b = Point { x: 2, y: 4 };
+        let c = Point { x: 3, y: 3 };
+        let points = [a, b, c];
+
+        let matrix = map
+            .distance_matrix(
+                &points,
+                Algorithm::Bfs,
+                Connectivity::Four,
+                &SearchBudget::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            matrix,
+            vec![
+                vec![Some(0), Some(3), None],
+                vec![Some(3), Some(0), None],
+                vec![None, None, Some(0)],
+            ]
+        );
+
+        for algorithm in [Algorithm::Bfs, Algorithm::Dijkstra] {
+            let matrix = map
+                .distance_matrix(
+                    &points,
+                    algorithm,
+                    Connectivity::Eight,
+                    &SearchBudget::default(),
+                )
+                .unwrap();
+            let graph = map.graph(Connectivity::Eight);
+            for (i, &source) in points.iter().enumerate() {
+                let tree = match algorithm {
+                    Algorithm::Dijkstra => graph::dijkstra(&graph, source, None),
+                    _ => graph::bfs(&graph, source, None),
+                };
+                for (j, &target) in points.iter().enumerate() {
+                    assert_eq!(matrix[i][j], tree.distances[graph.index(target)]);
+                }
+            }
+        }
+
+        let names = ["a".to_string(), "b, east".to_string(), "c".to_string()];
+        assert_eq!(
+            distance_matrix_csv(&names, &matrix),
+            ",a,\"b, east\",c\na,0,3,\n\"b, east\",3,0,\nc,,,0\n"
+        );
+    }
+
+    #[test]
+    fn test_bulk_run() {
+        let texts = [
+            "#####\n#i O#\n#####",
+            "#####\n#i#O#\n#####",
+            "bad\nmap!",
+            "#####\n#   #\n#####",
+        ];
+        let mut items: Vec<BulkItem> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| BulkItem {
+                name: format!("map{}", i),
+                input: BulkInput::Text(text.to_string()),
+            })
+            .collect();
+        items.push(BulkItem {
+            name: "stored".to_string(),
+            input: BulkInput::Map(
+                std::sync::Arc::new(Map::from_str(texts[0]).unwrap()),
+                std::sync::Arc::default(),
+            ),
+        });
+        items.push(BulkItem {
+            name: "missing".to_string(),
+            input: BulkInput::Unreadable("No such file".to_string()),
+        });
+
+        let solve = BulkOperation::Solve(Algorithm::Bfs.into());
+        let unlimited = BulkBudget::default();
+        let serial = bulk::run(&items, solve, &MapLimits::UNLIMITED, &unlimited, 1);
+        let names: Vec<&str> = serial.iter().map(|outcome| outcome.name.as_str()).collect();
+        assert_eq!(names, ["map0", "map1", "map2", "map3", "stored", "missing"]);
+
+        let results: Vec<_> = serial
+            .iter()
+            .map(|outcome| outcome.result.clone())
+            .collect();
+        let errors: Vec<_> = serial
+            .iter()
+            .map(|outcome| outcome.error.as_deref())
+            .collect();
+        assert_eq!(results[0].as_ref().unwrap()["length"], 2);
+        assert_eq!(results[1].as_ref().unwrap()["path_found"], false);
+        assert_eq!(results[4], results[0]);
+        assert_eq!(
+            errors,
+            [
+                None,
+                None,
+                Some("Invalid map format"),
+                Some("Map has no declared start and end"),
+                None,
+                Some("No such file"),
+            ]
+        );
+
+        // Порядок и итоги не зависят от числа потоков
+        let parallel = bulk::run(&items, solve, &MapLimits::UNLIMITED, &unlimited, 4);
+        assert_eq!(format!("{:?}", parallel), format!("{:?}", serial));
+
+        let tight = BulkBudget {
+            max_expanded: Some(1),
+            ..BulkBudget::default()
+        };
+        let limited = bulk::run(&items[..1], solve, &MapLimits::UNLIMITED, &tight, 2);
+        assert_eq!(
+            limited[0].error.as_deref(),
+            Some("Expanded node limit reached")
+        );
+        // Тот же предел распространяется и на метрики
+        let limited = bulk::run(
+            &items[..1],
+            BulkOperation::Metrics,
+            &MapLimits::UNLIMITED,
+            &tight,
+            2,
+        );
+        assert_eq!(
+            limited[0].error.as_deref(),
+            Some("Expanded node limit reached")
+        );
+
+        let validated = bulk::run(
+            &items,
+            BulkOperation::Validate,
+            &MapLimits::UNLIMITED,
+            &tight,
+            3,
+        );
+        assert_eq!(
+            validated[1].result.as_ref().unwrap()["endpoints_connected"],
+            false
+        );
+        assert_eq!(
+            validated[3].result.as_ref().unwrap()["has_endpoints"],
+            false
+        );
+
+        let small = MapLimits {
+            max_rows: 2,
+            ..MapLimits::UNLIMITED
+        };
+        let metrics = bulk::run(&items, BulkOperation::Metrics, &small, &unlimited, 2);
+        assert_eq!(
+            metrics[0].error.as_deref(),
+            Some("Map has more than 2 rows")
+        );
+        assert_eq!(metrics[4].result.as_ref().unwrap()["solution_length"], 2);
+
+        // Истёкший срок или отмена пакета останавливают все карты
+        let expired = BulkBudget {
+            deadline: Some(std::time::Instant::now()),
+            ..BulkBudget::default()
+        };
+        let cancelled = BulkBudget {
+            cancel: Some(CancelToken::default()),
+            ..BulkBudget::default()
+        };
+        cancelled.cancel.as_ref().unwrap().cancel();
+        for (budget, error) in [
+            (expired, "Time limit reached"),
+            (cancelled, "Search was cancelled"),
+        ] {
+            for operation in [solve, BulkOperation::Metrics, BulkOperation::Validate] {
+                let outcomes = bulk::run(&items, operation, &MapLimits::UNLIMITED, &budget, 2);
+                assert!(outcomes
+                    .iter()
+                    .all(|outcome| outcome.error.as_deref() == Some(error)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_constrained() {
+        // Два одинаково длинных пути: по верхнему и по нижнему коридору
+        let map = Map::from_str("#########\n#       #\n# ##### #\n#       #\n#########").unwrap();
+        let start = Point { x: 1, y: 1 };
+        let finish = Point { x: 3, y: 7 };
+        // Абстракция HPA* карты, общая для поисков без запрещённых клеток
+        let hierarchy = std::sync::OnceLock::new();
+        let solve_with = |algorithm: Algorithm, constraints: &PathConstraints| {
+            map.search_constrained(
+                start,
+                finish,
+                algorithm.into(),
+                constraints,
+                &hierarchy,
+                &SearchBudget::default(),
+            )
+            .unwrap()
+        };
+        let solve = |constraints: &PathConstraints| {
+            map.search_constrained(
+                start,
+                finish,
+                Algorithm::Bfs.into(),
+                constraints,
+                &hierarchy,
+                &SearchBudget::default(),
+            )
+            .unwrap()
+        };
+        let length = |constrained: &ConstrainedPath| {
+            constrained.result.path.as_ref().map(|path| path.len() - 1)
+        };
+
+        let free = solve(&PathConstraints::default());
+        assert_eq!(length(&free), Some(8));
+        assert_eq!(free.violated, None);
+
+        // Перекрытый верхний коридор уводит путь вниз; карта не меняется
+        let avoid_top = PathConstraints {
+            forbidden: vec![Point { x: 1, y: 4 }],
+            ..PathConstraints::default()
+        };
+        let detour = solve(&avoid_top);
+        assert_eq!(length(&detour), Some(8));
+        assert!(!detour
+            .result
+            .path
+            .as_ref()
+            .unwrap()
+            .contains(&Point { x: 1, y: 4 }));
+        assert!(map.is_open(Point { x: 1, y: 4 }));
+
+        let both = PathConstraints {
+            forbidden: vec![Point { x: 1, y: 4 }, Point { x: 3, y: 4 }],
+            ..PathConstraints::default()
+        };
+        assert_eq!(solve(&both).violated, Some(Constraint::ForbiddenCells));
+        assert_eq!(solve(&both).result.path, None);
+
+        let forbidden_start = PathConstraints {
+            forbidden: vec![start],
+            ..PathConstraints::default()
+        };
+        assert_eq!(
+            solve(&forbidden_start).violated,
+            Some(Constraint::ForbiddenCells)
+        );
+
+        // Путь через правый верхний угол
+        let corner = Region {
+            x: 1,
+            y: 6,
+            rows: 1,
+            cols: 2,
+        };
+        let through = solve(&PathConstraints {
+            region: Some(corner),
+            ..PathConstraints::default()
+        });
+        let path = through.result.path.unwrap();
+        assert_eq!(path.len() - 1, 8);
+        assert_eq!((path[0], path[path.len() - 1]), (start, finish));
+        assert!(path.contains(&Point { x: 1, y: 6 }) || path.contains(&Point { x: 1, y: 7 }));
+        assert!(path
+            .windows(2)
+            .all(|step| map.adjacent(step[0]).contains(&step[1])));
+
+        let walls = Region {
+            x: 0,
+            y: 0,
+            rows: 1,
+            cols: 9,
+        };
+        assert_eq!(
+            solve(&PathConstraints {
+                region: Some(walls),
+                ..PathConstraints::default()
+            })
+            .violated,
+            Some(Constraint::RequiredRegion)
+        );
+
+        let short = PathConstraints {
+            max_length: Some(7),
+            ..PathConstraints::default()
+        };
+        assert_eq!(solve(&short).violated, Some(Constraint::MaxLength));
+        assert_eq!(solve(&short).result.path, None);
+        let exact = PathConstraints {
+            max_length: Some(8),
+            ..PathConstraints::default()
+        };
+        assert_eq!(solve(&exact).violated, None);
+
+        // Алгоритмы с ценой ищут по той же копии с запрещёнными клетками
+        for algorithm in [
+            Algorithm::BidirectionalBfs,
+            Algorithm::Dijkstra,
+            Algorithm::AStar,
+            Algorithm::Jps,
+            Algorithm::Hpa,
+        ] {
+            let detour = solve_with(algorithm, &avoid_top);
+            assert_eq!(length(&detour), Some(8), "{:?}", algorithm);
+            assert!(!detour.result.path.unwrap().contains(&Point { x: 1, y: 4 }));
+            assert_eq!(
+                solve_with(algorithm, &both).violated,
+                Some(Constraint::ForbiddenCells)
+            );
+            assert_eq!(
+                solve_with(algorithm, &short).violated,
+                Some(Constraint::MaxLength)
+            );
+        }
+        // С `max_length` ищет BFS, и абстракция не нужна; без ограничений
+        // поиск берёт абстракцию карты
+        assert!(hierarchy.get().is_none());
+        solve_with(Algorithm::Hpa, &PathConstraints::default());
+        assert!(hierarchy.get().is_some());
+        let through = solve_with(
+            Algorithm::Dijkstra,
+            &PathConstraints {
+                region: Some(corner),
+                ..PathConstraints::default()
+            },
+        );
+        assert_eq!(length(&through), Some(8));
+
+        // Обрезанный по длине поиск всё равно называет настоящую причину
+        let short_region = PathConstraints {
+            region: Some(walls),
+            max_length: Some(2),
+            ..PathConstraints::default()
+        };
+        assert_eq!(
+            solve(&short_region).violated,
+            Some(Constraint::RequiredRegion)
+        );
+
+        // При 8-связности самый дешёвый путь длиннее по числу ходов (6
+        // против 5), но `max_length` считает ходы: путь в 5 ходов подходит
+        let map = Map::from_str(" # #  \n #  # \n#   # \n      \n      \n##    ").unwrap();
+        let (start, finish) = (Point { x: 2, y: 2 }, Point { x: 1, y: 0 });
+        let cheapest = map.search(
+            start,
+            finish,
+            SearchOptions {
+                algorithm: Algorithm::AStar,
+                connectivity: Connectivity::Eight,
+            },
+        );
+        assert_eq!(cheapest.path.map(|path| path.len() - 1), Some(6));
+        for algorithm in [Algorithm::Dijkstra, Algorithm::AStar, Algorithm::Jps] {
+            let fitting = map
+                .search_constrained(
+                    start,
+                    finish,
+                    SearchOptions {
+                        algorithm,
+                        connectivity: Connectivity::Eight,
+                    },
+                    &PathConstraints {
+                        max_length: Some(5),
+                        ..PathConstraints::default()
+                    },
+                    &std::sync::OnceLock::new(),
+                    &SearchBudget::default(),
+                )
+                .unwrap();
+            assert_eq!(fitting.violated, None, "{:?}", algorithm);
+            assert_eq!(length(&fitting), Some(5), "{:?}", algorithm);
+        }
+
+        // Без пути на самой карте ограничения не виноваты
+        let closed = Map::from_str("#####\n#i#O#\n#####").unwrap();
+        let none = closed
+            .search_constrained(
+                Point { x: 1, y: 1 },
+                Point { x: 1, y: 3 },
+                Algorithm::Bfs.into(),
+                &both,
+                &std::sync::OnceLock::new(),
+                &SearchBudget::default(),
+            )
+            .unwrap();
+        assert_eq!((none.result.path, none.violated), (None, None));
     }
 }